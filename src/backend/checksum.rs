@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The result of comparing the checksum embedded within a file name against the checksum of the file's contents.
+#[derive(Debug, PartialEq)]
+pub enum Verification {
+    Match(u32),
+    Mismatch { expected: u32, actual: u32 },
+    NoChecksum
+}
+
+/// Generates the lookup table for the reflected IEEE 802.3 polynomial used by CRC32.
+fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    for (index, entry) in table.iter_mut().enumerate() {
+        let mut value = index as u32;
+        for _ in 0..8 {
+            value = if value & 1 == 1 { 0xEDB88320 ^ (value >> 1) } else { value >> 1 };
+        }
+        *entry = value;
+    }
+    table
+}
+
+/// Computes the CRC32 checksum of everything that can be read from the reader, streaming it in small blocks so
+/// that large files do not need to be held in memory.
+pub fn crc32<R: Read>(mut reader: R) -> io::Result<u32> {
+    let table = crc32_table();
+    let mut crc = !0u32;
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(ref why) if why.kind() == io::ErrorKind::Interrupted => continue,
+            Err(why) => return Err(why)
+        };
+        for &byte in &buffer[..read] {
+            crc = table[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+    }
+    Ok(!crc)
+}
+
+/// Computes the CRC32 checksum of the file at the given path.
+pub fn crc32_file(path: &Path) -> io::Result<u32> {
+    File::open(path).and_then(crc32)
+}
+
+/// Finds a CRC32 checksum embedded within a file name, which is written as eight hexadecimal digits enclosed in
+/// brackets or parentheses, as in `[ABCD1234]`.
+pub fn embedded_checksum(name: &str) -> Option<u32> {
+    name.split(['[', '(']).skip(1)
+        .filter_map(|field| field.split([']', ')']).next())
+        .filter(|field| field.len() == 8 && field.chars().all(|c| c.is_ascii_hexdigit()))
+        .filter_map(|field| u32::from_str_radix(field, 16).ok())
+        .last()
+}
+
+/// Compares the checksum embedded within the file's name, if there is one, against the file's contents.
+pub fn verify_checksum(path: &Path) -> io::Result<Verification> {
+    let expected = match path.file_name().and_then(|name| embedded_checksum(&name.to_string_lossy())) {
+        Some(expected) => expected,
+        None => return Ok(Verification::NoChecksum)
+    };
+
+    let actual = crc32_file(path)?;
+    Ok(if actual == expected { Verification::Match(actual) } else { Verification::Mismatch { expected: expected, actual: actual } })
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(&b"123456789"[..]).unwrap(), 0xCBF43926);
+    assert_eq!(crc32(&b""[..]).unwrap(), 0);
+}
+
+#[test]
+fn test_embedded_checksum() {
+    assert_eq!(embedded_checksum("[Group] Series - 01 [1080p][ABCD1234].mkv"), Some(0xABCD1234));
+    assert_eq!(embedded_checksum("[Group] Series - 01 (abcd1234).mkv"), Some(0xABCD1234));
+    assert_eq!(embedded_checksum("[Group] Series - 01 [1080p].mkv"), None);
+}
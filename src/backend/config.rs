@@ -0,0 +1,100 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use super::rewrite::{self, Rewrite, RewriteError};
+use super::rules::{self, Rule, RuleError};
+use super::tokenizer;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ConfigError {
+        NoConfigDir {
+            display("unable to determine the configuration directory")
+        }
+        Read(file: PathBuf, err: io::Error) {
+            display("unable to read {:?}: {}", file, err)
+        }
+        Rule(err: RuleError) {
+            display("invalid rule in the rules file: {}", err)
+        }
+        Rewrite(err: RewriteError) {
+            display("invalid rewrite in the rewrites file: {}", err)
+        }
+    }
+}
+
+/// Obtains the directory where configuration files are stored, according to the XDG base directory specification.
+pub fn config_directory() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home| home.join(".config")))
+        .map(|directory| directory.join("tv-renamer"))
+}
+
+/// Reads the contents of a file in the configuration directory. A file which does not exist is treated as empty.
+pub fn read_config(name: &str) -> Result<String, ConfigError> {
+    let path = config_directory().ok_or(ConfigError::NoConfigDir)?.join(name);
+    let mut contents = String::new();
+    match File::open(&path) {
+        Ok(mut file) => { file.read_to_string(&mut contents).map_err(|why| ConfigError::Read(path, why))?; },
+        Err(ref why) if why.kind() == io::ErrorKind::NotFound => (),
+        Err(why) => return Err(ConfigError::Read(path, why))
+    }
+    Ok(contents)
+}
+
+/// Parses a list of presets, where each line is written as `name = template`. Blank lines and lines beginning
+/// with `#` are ignored.
+pub fn parse_presets(contents: &str) -> Vec<(String, String)> {
+    contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.splitn(2, '=');
+            match (fields.next(), fields.next()) {
+                (Some(name), Some(template)) => Some((name.trim().to_owned(), template.trim().to_owned())),
+                _ => None
+            }
+        })
+        .collect()
+}
+
+/// Collects all of the built-in presets, followed by the user's presets from `~/.config/tv-renamer/presets`.
+/// User presets which share a name with a built-in preset will replace the built-in preset.
+pub fn presets() -> Result<Vec<(String, String)>, ConfigError> {
+    let mut presets: Vec<(String, String)> = tokenizer::PRESETS.iter()
+        .map(|&(name, template)| (name.to_owned(), template.to_owned()))
+        .collect();
+
+    for (name, template) in parse_presets(&read_config("presets")?) {
+        match presets.iter().position(|(key, _)| key == &name) {
+            Some(index) => presets[index].1 = template,
+            None => presets.push((name, template))
+        }
+    }
+
+    Ok(presets)
+}
+
+/// Obtains the template of a preset by its name, searching the user's presets before the built-in presets.
+pub fn preset(name: &str) -> Result<Option<String>, ConfigError> {
+    presets().map(|presets| presets.into_iter().find(|(key, _)| key == name).map(|(_, template)| template))
+}
+
+/// Reads the user's routing rules from `~/.config/tv-renamer/rules`, one rule per line, in the order that they
+/// should be evaluated.
+pub fn rules() -> Result<Vec<Rule>, ConfigError> {
+    rules::parse_rules(&read_config("rules")?).map_err(ConfigError::Rule)
+}
+
+/// Reads the user's title rewrites from `~/.config/tv-renamer/rewrites`, in the order that they should be applied.
+pub fn rewrites() -> Result<Vec<Rewrite>, ConfigError> {
+    rewrite::parse_rewrites(&read_config("rewrites")?).map_err(ConfigError::Rewrite)
+}
+
+#[test]
+fn test_parse_presets() {
+    let presets = parse_presets("# comment\n\narchive = ${Series} ${Season}x${Episode}\ninvalid line\n");
+    assert_eq!(presets, vec![("archive".to_owned(), "${Series} ${Season}x${Episode}".to_owned())]);
+}
@@ -0,0 +1,447 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::plan;
+use super::transfer::{self, TransferMode};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum JournalError {
+        NoDataDir {
+            display("unable to determine the data directory")
+        }
+        Io(file: PathBuf, err: io::Error) {
+            display("unable to access {:?}: {}", file, err)
+        }
+        Corrupt(line: String) {
+            display("the journal contains an invalid line: `{}`", line)
+        }
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum UndoError {
+        Journal(err: JournalError) {
+            from()
+            display("{}", err)
+        }
+        Empty {
+            display("there are no renames to undo")
+        }
+        NoEntry(id: u32) {
+            display("the journal does not contain an entry with the ID {}", id)
+        }
+        Changed(problems: Vec<String>) {
+            display("refusing to undo, as files have changed since they were renamed:\n{}", problems.join("\n"))
+        }
+        Failed(source: PathBuf, target: PathBuf, err: io::Error) {
+            display("unable to rename {:?} back to {:?}: {}", target, source, err)
+        }
+        Remove(target: PathBuf, err: io::Error) {
+            display("unable to remove {:?}: {}", target, err)
+        }
+    }
+}
+
+/// A rename which was recorded in the journal, along with the size and modification time of the file after it
+/// was renamed. Directories are recorded without a size or modification time, as renaming the files within them
+/// changes their modification time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalRename {
+    pub source:          PathBuf,
+    pub target:          PathBuf,
+    pub size:            Option<u64>,
+    pub modified:        Option<u64>,
+    /// The modification time of the original file before the run changed it, such as to the air date of the
+    /// episode, which is restored when the rename is undone.
+    pub previous:        Option<u64>,
+    /// The size and modification time of the source after the run, when the transfer kept the source in place.
+    pub source_size:     Option<u64>,
+    pub source_modified: Option<u64>
+}
+
+/// Every rename which was applied by a single run of the application.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub id:        u32,
+    /// The time at which the renames were applied, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub series:    String,
+    /// How the episodes were transferred, which determines how the renames are reverted.
+    pub transfer:  TransferMode,
+    pub renames:   Vec<JournalRename>
+}
+
+/// Obtains the directory where application data is stored, according to the XDG base directory specification.
+pub fn data_directory() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home| home.join(".local/share")))
+        .map(|directory| directory.join("tv-renamer"))
+}
+
+fn journal_path() -> Result<PathBuf, JournalError> {
+    data_directory().map(|directory| directory.join("journal")).ok_or(JournalError::NoDataDir)
+}
+
+/// Tabs, newlines, and backslashes within fields are escaped, as fields are separated by tabs.
+fn escape(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut output = String::with_capacity(field.len());
+    let mut characters = field.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' { output.push(character); continue }
+        match characters.next() {
+            Some('t') => output.push('\t'),
+            Some('n') => output.push('\n'),
+            Some(character) => output.push(character),
+            None => output.push('\\')
+        }
+    }
+    output
+}
+
+fn optional_field(field: Option<u64>) -> String {
+    field.map_or_else(|| String::from("-"), |value| value.to_string())
+}
+
+/// Reads the size and modification time of a file, or neither if the path is a directory.
+fn file_stamp(path: &Path) -> io::Result<(Option<u64>, Option<u64>)> {
+    let metadata = fs::metadata(path)?;
+    if metadata.is_dir() { return Ok((None, None)) }
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    Ok((Some(metadata.len()), Some(modified)))
+}
+
+/// Serializes an entry, where the first line describes the entry and each following line describes a rename.
+fn format_entry(entry: &Entry) -> String {
+    let mut output = format!("entry\t{}\t{}\t{}\t{}\n", entry.id, entry.timestamp, escape(&entry.series),
+        entry.transfer.name());
+    for rename in &entry.renames {
+        output.push_str(&format!("rename\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            escape(&rename.source.to_string_lossy()), escape(&rename.target.to_string_lossy()),
+            optional_field(rename.size), optional_field(rename.modified), optional_field(rename.previous),
+            optional_field(rename.source_size), optional_field(rename.source_modified)));
+    }
+    output
+}
+
+/// Parses the contents of the journal, in the order that the entries were recorded.
+pub fn parse_journal(contents: &str) -> Result<Vec<Entry>, JournalError> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in contents.lines().filter(|line| !line.is_empty()) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let corrupt = || JournalError::Corrupt(line.to_owned());
+        let optional = |field: &str| if field == "-" { Ok(None) } else { field.parse::<u64>().map(Some) };
+        match fields.as_slice() {
+            // Entries which were recorded before transfer modes were introduced do not record a mode.
+            ["entry", id, timestamp, series] | ["entry", id, timestamp, series, _] => entries.push(Entry {
+                id:        id.parse::<u32>().map_err(|_| corrupt())?,
+                timestamp: timestamp.parse::<u64>().map_err(|_| corrupt())?,
+                series:    unescape(series),
+                transfer:  match fields.get(4) {
+                    Some(mode) => TransferMode::from_name(mode).ok_or_else(corrupt)?,
+                    None => TransferMode::Rename
+                },
+                renames:   Vec::new()
+            }),
+            // Renames which were recorded before previous modification times and the stamps of kept sources were
+            // introduced do not record them.
+            ["rename", source, target, size, modified]
+                | ["rename", source, target, size, modified, _]
+                | ["rename", source, target, size, modified, _, _, _] =>
+            {
+                let field = |index: usize| optional(fields.get(index).cloned().unwrap_or("-")).map_err(|_| corrupt());
+                entries.last_mut().ok_or_else(corrupt)?.renames.push(JournalRename {
+                    source:          PathBuf::from(unescape(source)),
+                    target:          PathBuf::from(unescape(target)),
+                    size:            optional(size).map_err(|_| corrupt())?,
+                    modified:        optional(modified).map_err(|_| corrupt())?,
+                    previous:        field(5)?,
+                    source_size:     field(6)?,
+                    source_modified: field(7)?
+                })
+            },
+            _ => return Err(corrupt())
+        }
+    }
+    Ok(entries)
+}
+
+/// Reads every entry within the journal. A journal which does not exist is treated as empty.
+pub fn entries() -> Result<Vec<Entry>, JournalError> {
+    let path = journal_path()?;
+    let mut contents = String::new();
+    match File::open(&path) {
+        Ok(mut file) => { file.read_to_string(&mut contents).map_err(|why| JournalError::Io(path, why))?; },
+        Err(ref why) if why.kind() == io::ErrorKind::NotFound => (),
+        Err(why) => return Err(JournalError::Io(path, why))
+    }
+    parse_journal(&contents)
+}
+
+/// Appends the renames which were applied with the given transfer mode, in the order that they were applied, as a
+/// new entry in the journal, along with the previous modification time of each target whose time was changed.
+/// Returns the ID of the new entry.
+pub fn record(series: &str, transfer: TransferMode, renames: &[(PathBuf, PathBuf)], previous: &[(PathBuf, i64)])
+    -> Result<u32, JournalError>
+{
+    let path = journal_path()?;
+    let id = entries()?.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+    // Paths are recorded as absolute paths, so that they may be undone from any working directory.
+    let directory = env::current_dir().map_err(|why| JournalError::Io(PathBuf::from("."), why))?;
+
+    let mut entry = Entry {
+        id:        id,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0),
+        series:    series.to_owned(),
+        transfer:  transfer,
+        renames:   Vec::with_capacity(renames.len())
+    };
+    for (source, target) in renames {
+        entry.renames.push(JournalRename {
+            source:          directory.join(source),
+            target:          directory.join(target),
+            size:            None,
+            modified:        None,
+            previous:        previous.iter().find(|(path, _)| path == target).map(|&(_, time)| time as u64),
+            source_size:     None,
+            source_modified: None
+        });
+    }
+    // Targets are stamped where they are now, as the directories that contain them may have been renamed since.
+    // Sources which were kept are stamped as well, so that their targets are only removed while they are intact.
+    for index in 0..entry.renames.len() {
+        let later = &entry.renames[index + 1..];
+        let target = resolve(later, &entry.renames[index].target);
+        let (size, modified) = file_stamp(&target).map_err(|why| JournalError::Io(target.clone(), why))?;
+        let (source_size, source_modified) = if transfer.removes_source() { (None, None) } else {
+            let source = resolve(later, &entry.renames[index].source);
+            file_stamp(&source).map_err(|why| JournalError::Io(source.clone(), why))?
+        };
+        let rename = &mut entry.renames[index];
+        rename.size = size;
+        rename.modified = modified;
+        rename.source_size = source_size;
+        rename.source_modified = source_modified;
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|why| JournalError::Io(parent.to_path_buf(), why))?;
+    }
+    OpenOptions::new().create(true).append(true).open(&path)
+        .and_then(|mut file| file.write_all(format_entry(&entry).as_bytes()))
+        .map_err(|why| JournalError::Io(path, why))?;
+    Ok(id)
+}
+
+/// Obtains the current location of a path that was recorded by a rename, as the directories that contain it may
+/// have been renamed by the renames which followed it, such as when a season directory is renamed after its
+/// episodes.
+fn resolve(later: &[JournalRename], path: &Path) -> PathBuf {
+    later.iter().fold(path.to_path_buf(), |path, rename| match path.strip_prefix(&rename.source) {
+        Ok(rest) => rename.target.join(rest),
+        Err(_) => path.clone()
+    })
+}
+
+/// Checks that every target of an entry is still in place and unchanged. When the source was moved, nothing may
+/// have since taken its place, and when the source was kept, it must still be in place and unchanged, so that
+/// removing the target never removes the only copy of an episode. Returns a description of every problem that
+/// was found.
+fn check_entry(entry: &Entry) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (index, rename) in entry.renames.iter().enumerate() {
+        let later = &entry.renames[index + 1..];
+        let target = resolve(later, &rename.target);
+        match file_stamp(&target) {
+            Ok((size, modified)) => if size != rename.size || modified != rename.modified {
+                problems.push(format!("{:?} has been modified", target));
+            },
+            Err(_) => problems.push(format!("{:?} no longer exists", target))
+        }
+
+        let source = resolve(later, &rename.source);
+        if !entry.transfer.removes_source() {
+            // Sources which were recorded before their stamps were introduced are only required to exist.
+            match file_stamp(&source) {
+                Ok((size, modified)) => if rename.source_size.is_some()
+                    && (size != rename.source_size || modified != rename.source_modified)
+                {
+                    problems.push(format!("{:?} has been modified", source));
+                },
+                Err(_) => problems.push(format!("{:?} no longer exists", source))
+            }
+            continue
+        }
+
+        // The source may be occupied by the target of another rename within the same entry.
+        let occupied = entry.renames.iter().enumerate()
+            .any(|(other, rename)| other != index && resolve(&entry.renames[other + 1..], &rename.target) == source);
+        if source.exists() && !occupied {
+            problems.push(format!("{:?} already exists", source));
+        }
+    }
+    problems
+}
+
+/// Reverts the renames of the most recent entry in the journal, or the entry with the given ID, in reverse order.
+/// Episodes which were copied or linked are reverted by removing their targets. Nothing is reverted if any of the
+/// files have changed or moved since they were renamed. Once reverted, the entry is removed from the journal.
+pub fn undo(id: Option<u32>) -> Result<Entry, UndoError> {
+    let mut entries = entries()?;
+    let index = match id {
+        Some(id) => entries.iter().position(|entry| entry.id == id).ok_or(UndoError::NoEntry(id))?,
+        None if entries.is_empty() => return Err(UndoError::Empty),
+        None => entries.len() - 1
+    };
+
+    let problems = check_entry(&entries[index]);
+    if !problems.is_empty() { return Err(UndoError::Changed(problems)) }
+
+    // Every rename is reverted in reverse order, so each path is where it was recorded by the time it is reverted.
+    // If any rename cannot be reverted, the renames which were reverted are performed again.
+    let entry = entries.remove(index);
+    if entry.transfer.removes_source() {
+        let renames: Vec<(PathBuf, PathBuf)> = entry.renames.iter().rev()
+            .map(|rename| (rename.target.clone(), rename.source.clone()))
+            .collect();
+        plan::apply_renames(&renames, entry.transfer, |_, _, _| ())
+            .map_err(|why| UndoError::Failed(why.target, why.source, why.why))?;
+    } else {
+        for rename in entry.renames.iter().rev() {
+            transfer::revert(&rename.source, &rename.target, entry.transfer)
+                .map_err(|why| UndoError::Remove(rename.target.clone(), why))?;
+        }
+    }
+
+    // The original files are back at their sources, where the times that the run changed are restored. A time
+    // which cannot be restored does not prevent the entry from being removed, as the renames were reverted.
+    for rename in &entry.renames {
+        if let Some(time) = rename.previous { let _ = transfer::set_modified(&rename.source, time as i64); }
+    }
+
+    let contents: String = entries.iter().map(format_entry).collect();
+    replace_journal(&contents)?;
+    Ok(entry)
+}
+
+/// Replaces the contents of the journal by writing them to a temporary file within the same directory, which is
+/// then renamed over the journal, so that the journal is never left truncated if the write fails.
+fn replace_journal(contents: &str) -> Result<(), JournalError> {
+    let path = journal_path()?;
+    let temporary = path.with_file_name(format!(".journal-{}.tmp", process::id()));
+    let written = File::create(&temporary)
+        .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temporary, &path));
+    if let Err(why) = written {
+        let _ = fs::remove_file(&temporary);
+        return Err(JournalError::Io(path, why));
+    }
+    Ok(())
+}
+
+/// Formats a timestamp as a date and time in UTC, such as `2017-03-04 18:30 UTC`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86400, timestamp % 86400);
+    // Converts the days since the Unix epoch into a civil date, from Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, seconds / 3600, seconds % 3600 / 60)
+}
+
+#[test]
+fn test_journal_format() {
+    let entry = Entry {
+        id:        3,
+        timestamp: 1488652200,
+        series:    String::from("Series\tName"),
+        transfer:  TransferMode::Move,
+        renames:   vec![
+            JournalRename {
+                source:          PathBuf::from("/videos/one.mkv"),
+                target:          PathBuf::from("/videos/Series - 1x01.mkv"),
+                size:            Some(1024),
+                modified:        Some(1488650000),
+                previous:        Some(1288650000),
+                source_size:     Some(1024),
+                source_modified: Some(1288650000)
+            },
+            JournalRename {
+                source:          PathBuf::from("/videos/Season1"),
+                target:          PathBuf::from("/videos/Season 01"),
+                size:            None,
+                modified:        None,
+                previous:        None,
+                source_size:     None,
+                source_modified: None
+            }
+        ]
+    };
+    assert_eq!(parse_journal(&format_entry(&entry)).unwrap(), vec![entry]);
+    assert!(parse_journal("rename\t/a\t/b\t-\t-\n").is_err());
+    assert_eq!(parse_journal("entry\t1\t0\tSeries\nrename\t/a\t/b\t-\t-\n").unwrap()[0].renames[0].previous, None);
+    assert_eq!(parse_journal("entry\t1\t0\tSeries\n").unwrap()[0].transfer, TransferMode::Rename);
+    assert_eq!(format_timestamp(1488652200), "2017-03-04 18:30 UTC");
+}
+
+#[test]
+fn test_check_kept_source() {
+    let directory = super::TempDir::new("journal");
+    fs::write(directory.join("one.mkv"), b"episode").unwrap();
+    fs::write(directory.join("1x01.mkv"), b"episode").unwrap();
+    let stamp = |name: &str| file_stamp(&directory.join(name)).unwrap();
+    let ((size, modified), (source_size, source_modified)) = (stamp("1x01.mkv"), stamp("one.mkv"));
+    let entry = Entry {
+        id:        1,
+        timestamp: 0,
+        series:    String::from("Series"),
+        transfer:  TransferMode::Copy,
+        renames:   vec![JournalRename {
+            source:          directory.join("one.mkv"),
+            target:          directory.join("1x01.mkv"),
+            size:            size,
+            modified:        modified,
+            previous:        None,
+            source_size:     source_size,
+            source_modified: source_modified
+        }]
+    };
+    assert!(check_entry(&entry).is_empty());
+
+    // The copy is not removed once the original has changed or is gone, as it may be the only copy left.
+    fs::write(directory.join("one.mkv"), b"changed episode").unwrap();
+    assert_eq!(check_entry(&entry), vec![format!("{:?} has been modified", directory.join("one.mkv"))]);
+    fs::remove_file(directory.join("one.mkv")).unwrap();
+    assert_eq!(check_entry(&entry), vec![format!("{:?} no longer exists", directory.join("one.mkv"))]);
+}
+
+#[test]
+fn test_resolve() {
+    let rename = |source: &str, target: &str| JournalRename {
+        source:          PathBuf::from(source),
+        target:          PathBuf::from(target),
+        size:            None,
+        modified:        None,
+        previous:        None,
+        source_size:     None,
+        source_modified: None
+    };
+    let later = [rename("/videos/Season1", "/videos/Season 01"), rename("/videos", "/Series (2005)")];
+    assert_eq!(resolve(&later, Path::new("/videos/Season1/one.mkv")), PathBuf::from("/Series (2005)/Season 01/one.mkv"));
+    assert_eq!(resolve(&later, Path::new("/other/one.mkv")), PathBuf::from("/other/one.mkv"));
+}
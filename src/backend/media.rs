@@ -0,0 +1,437 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Technical information about the first video and audio tracks of a media file, as recorded by its container.
+#[derive(Debug, Default, PartialEq)]
+pub struct MediaInfo {
+    pub video_height:   Option<u64>,
+    pub video_codec:    Option<String>,
+    pub audio_codec:    Option<String>,
+    pub audio_channels: Option<u64>,
+    pub hdr:            Option<&'static str>,
+    /// The duration of the media, in seconds.
+    pub duration:       Option<u64>
+}
+
+impl MediaInfo {
+    /// Describes the number of audio channels as a speaker layout, such as `2.0` or `5.1`.
+    pub fn channel_layout(&self) -> Option<String> {
+        self.audio_channels.map(|channels| match channels {
+            6 => "5.1".to_owned(),
+            8 => "7.1".to_owned(),
+            _ => format!("{}.0", channels)
+        })
+    }
+}
+
+/// Reads the headers of a Matroska or MP4 file. Files in any other format will return an empty `MediaInfo`.
+pub fn read_media_info(path: &Path) -> io::Result<MediaInfo> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 8];
+    if reader.read_exact(&mut magic).is_err() { return Ok(MediaInfo::default()) }
+    reader.seek(SeekFrom::Start(0))?;
+
+    if magic[..4] == [0x1A, 0x45, 0xDF, 0xA3] {
+        read_matroska(&mut reader)
+    } else if &magic[4..] == b"ftyp" {
+        read_mp4(&mut reader)
+    } else {
+        Ok(MediaInfo::default())
+    }
+}
+
+/// Interprets the transfer characteristics of the video's colour information, as defined by ISO/IEC 23001-8.
+fn transfer_to_hdr(transfer: u64) -> Option<&'static str> {
+    match transfer {
+        16 => Some("HDR10"),
+        18 => Some("HLG"),
+        _  => None
+    }
+}
+
+/// Ignores the remainder of the file once the end of the file has been reached unexpectedly.
+fn ignore_eof(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(ref why) if why.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+        result => result
+    }
+}
+
+/// Reports a size which cannot be correct, so that a corrupt file is not read beyond its bounds.
+fn invalid_size() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid element size")
+}
+
+fn position<R: Seek>(reader: &mut R) -> io::Result<u64> {
+    reader.stream_position()
+}
+
+fn read_uint<R: Read>(reader: &mut R, size: u64) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut byte = [0u8; 1];
+    for _ in 0..size.min(8) {
+        reader.read_exact(&mut byte)?;
+        value = (value << 8) | byte[0] as u64;
+    }
+    Ok(value)
+}
+
+fn read_string<R: Read>(reader: &mut R, size: u64) -> io::Result<String> {
+    let mut buffer = vec![0u8; size.min(256) as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).trim_end_matches('\0').to_owned())
+}
+
+// Matroska element IDs, as defined by the Matroska specification.
+const EBML_HEADER: u64              = 0x1A45DFA3;
+const SEGMENT: u64                  = 0x18538067;
+const INFO: u64                     = 0x1549A966;
+const TIMECODE_SCALE: u64           = 0x2AD7B1;
+const DURATION: u64                 = 0x4489;
+const TRACKS: u64                   = 0x1654AE6B;
+const TRACK_ENTRY: u64              = 0xAE;
+const TRACK_TYPE: u64               = 0x83;
+const CODEC_ID: u64                 = 0x86;
+const VIDEO: u64                    = 0xE0;
+const PIXEL_HEIGHT: u64             = 0xBA;
+const COLOUR: u64                   = 0x55B0;
+const TRANSFER_CHARACTERISTICS: u64 = 0x55BA;
+const AUDIO: u64                    = 0xE1;
+const CHANNELS: u64                 = 0x9F;
+const CLUSTER: u64                  = 0x1F43B675;
+
+/// A size whose value bits are all set marks an element of unknown size, which extends to the end of its parent.
+const UNKNOWN_SIZE: u64 = u64::MAX;
+
+#[derive(Clone, Copy)]
+struct Element {
+    id:   u64,
+    size: u64
+}
+
+/// Reads an EBML variable-length integer, optionally keeping the length marker, as is the case with element IDs.
+fn read_vint<R: Read>(reader: &mut R, keep_marker: bool) -> io::Result<u64> {
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte)?;
+    let length = byte[0].leading_zeros() as u64 + 1;
+    if length > 8 { return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid EBML integer")) }
+
+    let first = if keep_marker { byte[0] as u64 } else { (byte[0] as u64) & (0xFF >> length) };
+    let value = (first << ((length - 1) * 8)) | read_uint(reader, length - 1)?;
+
+    if !keep_marker && value == (1 << (length * 7)) - 1 { Ok(UNKNOWN_SIZE) } else { Ok(value) }
+}
+
+fn read_element<R: Read>(reader: &mut R) -> io::Result<Element> {
+    let id = read_vint(reader, true)?;
+    let size = read_vint(reader, false)?;
+    Ok(Element { id: id, size: size })
+}
+
+fn read_float<R: Read>(reader: &mut R, size: u64) -> io::Result<f64> {
+    match size {
+        4 => read_uint(reader, 4).map(|bits| f32::from_bits(bits as u32) as f64),
+        8 => read_uint(reader, 8).map(f64::from_bits),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid EBML float"))
+    }
+}
+
+/// Calls `action` for every child element within the next `size` bytes. The reader is positioned at the end of each
+/// child after `action` returns, so `action` only needs to read the children that it is interested in.
+fn for_each_element<R, F>(reader: &mut R, size: u64, mut action: F) -> io::Result<()>
+    where R: Read + Seek, F: FnMut(&mut R, Element) -> io::Result<bool>
+{
+    let end = position(reader)?.saturating_add(size);
+    while position(reader)? < end {
+        let element = read_element(reader)?;
+        let start = position(reader)?;
+        if !action(reader, element)? || element.size == UNKNOWN_SIZE { break }
+        reader.seek(SeekFrom::Start(start.checked_add(element.size).ok_or_else(invalid_size)?))?;
+    }
+    Ok(())
+}
+
+fn read_matroska<R: Read + Seek>(reader: &mut R) -> io::Result<MediaInfo> {
+    let mut info = MediaInfo::default();
+    let mut scale = 1_000_000;
+    let mut duration = None;
+
+    ignore_eof(for_each_element(reader, UNKNOWN_SIZE, |reader, element| match element.id {
+        EBML_HEADER => Ok(true),
+        SEGMENT => for_each_element(reader, element.size, |reader, element| match element.id {
+            INFO => for_each_element(reader, element.size, |reader, element| {
+                match element.id {
+                    TIMECODE_SCALE => scale = read_uint(reader, element.size)?,
+                    DURATION       => duration = Some(read_float(reader, element.size)?),
+                    _              => ()
+                }
+                Ok(true)
+            }).map(|_| true),
+            TRACKS => for_each_element(reader, element.size, |reader, element| {
+                if element.id == TRACK_ENTRY { read_matroska_track(reader, element.size, &mut info)?; }
+                Ok(true)
+            }).map(|_| true),
+            // Clusters contain the media itself, and the headers will always precede them.
+            CLUSTER => Ok(false),
+            _ => Ok(true)
+        }).map(|_| false),
+        _ => Ok(false)
+    }))?;
+
+    info.duration = duration.map(|duration: f64| (duration * scale as f64 / 1_000_000_000f64) as u64);
+    Ok(info)
+}
+
+fn read_matroska_track<R: Read + Seek>(reader: &mut R, size: u64, info: &mut MediaInfo) -> io::Result<()> {
+    let mut kind = 0;
+    let mut codec = None;
+    let mut height = None;
+    let mut transfer = None;
+    let mut channels = None;
+
+    for_each_element(reader, size, |reader, element| {
+        match element.id {
+            TRACK_TYPE => kind = read_uint(reader, element.size)?,
+            CODEC_ID   => codec = Some(read_string(reader, element.size)?),
+            VIDEO      => for_each_element(reader, element.size, |reader, element| {
+                match element.id {
+                    PIXEL_HEIGHT => height = Some(read_uint(reader, element.size)?),
+                    COLOUR       => for_each_element(reader, element.size, |reader, element| {
+                        if element.id == TRANSFER_CHARACTERISTICS { transfer = Some(read_uint(reader, element.size)?); }
+                        Ok(true)
+                    })?,
+                    _ => ()
+                }
+                Ok(true)
+            })?,
+            AUDIO      => for_each_element(reader, element.size, |reader, element| {
+                if element.id == CHANNELS { channels = Some(read_uint(reader, element.size)?); }
+                Ok(true)
+            })?,
+            _ => ()
+        }
+        Ok(true)
+    })?;
+
+    match kind {
+        1 if info.video_codec.is_none() => {
+            info.video_codec = codec.map(|codec| matroska_codec(&codec));
+            info.video_height = height;
+            info.hdr = transfer.and_then(transfer_to_hdr);
+        },
+        2 if info.audio_codec.is_none() => {
+            info.audio_codec = codec.map(|codec| matroska_codec(&codec));
+            info.audio_channels = channels.or(Some(1));
+        },
+        _ => ()
+    }
+
+    Ok(())
+}
+
+/// Converts a Matroska codec ID, such as `V_MPEGH/ISO/HEVC`, into the common name of the codec.
+fn matroska_codec(codec: &str) -> String {
+    let name = match codec {
+        "V_MPEG4/ISO/AVC"  => "H.264",
+        "V_MPEGH/ISO/HEVC" => "HEVC",
+        "V_MPEG4/ISO/ASP"  => "MPEG-4",
+        "V_MPEG2"          => "MPEG-2",
+        "V_AV1"            => "AV1",
+        "V_VP8"            => "VP8",
+        "V_VP9"            => "VP9",
+        "A_AC3"            => "AC3",
+        "A_EAC3"           => "EAC3",
+        "A_TRUEHD"         => "TrueHD",
+        "A_FLAC"           => "FLAC",
+        "A_OPUS"           => "Opus",
+        "A_VORBIS"         => "Vorbis",
+        "A_MPEG/L3"        => "MP3",
+        _ if codec.starts_with("A_AAC") => "AAC",
+        _ if codec.starts_with("A_DTS") => "DTS",
+        _ if codec.starts_with("A_PCM") => "PCM",
+        // Strip the `V_` or `A_` prefix from codecs that are not known.
+        _ => return codec.splitn(2, '_').last().unwrap_or(codec).to_owned()
+    };
+    name.to_owned()
+}
+
+/// Calls `action` with the type and content size of every box within the next `size` bytes. The reader is
+/// positioned at the end of each box after `action` returns.
+fn for_each_box<R, F>(reader: &mut R, size: u64, mut action: F) -> io::Result<()>
+    where R: Read + Seek, F: FnMut(&mut R, &[u8; 4], u64) -> io::Result<()>
+{
+    let end = position(reader)?.saturating_add(size);
+    while position(reader)?.saturating_add(8) <= end {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let size = match read_uint(&mut &header[..4], 4)? {
+            0 => end - position(reader)?,
+            1 => read_uint(reader, 8)?.saturating_sub(16),
+            size => size.saturating_sub(8)
+        };
+        let start = position(reader)?;
+        action(reader, &kind, size)?;
+        reader.seek(SeekFrom::Start(start.checked_add(size).ok_or_else(invalid_size)?))?;
+    }
+    Ok(())
+}
+
+fn read_mp4<R: Read + Seek>(reader: &mut R) -> io::Result<MediaInfo> {
+    let mut info = MediaInfo::default();
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    ignore_eof(read_mp4_boxes(reader, end, &mut info))?;
+    Ok(info)
+}
+
+/// Descends through the boxes which lead to the movie header and the sample descriptions of each track.
+fn read_mp4_boxes<R: Read + Seek>(reader: &mut R, size: u64, info: &mut MediaInfo) -> io::Result<()> {
+    for_each_box(reader, size, |reader, kind, size| {
+        match kind {
+            b"moov" | b"trak" | b"mdia" | b"minf" | b"stbl" => read_mp4_boxes(reader, size, info)?,
+            b"mvhd" => {
+                let version = read_uint(reader, 4)? >> 24;
+                let (skip, length) = if version == 1 { (16, 8) } else { (8, 4) };
+                reader.seek(SeekFrom::Current(skip))?;
+                let timescale = read_uint(reader, 4)?;
+                let duration = read_uint(reader, length)?;
+                if let Some(seconds) = duration.checked_div(timescale) { info.duration = Some(seconds); }
+            },
+            b"stsd" => {
+                // Skip the version, flags, and entry count which precede the sample entries.
+                let entries = size.checked_sub(8).ok_or_else(invalid_size)?;
+                reader.seek(SeekFrom::Current(8))?;
+                for_each_box(reader, entries, |reader, kind, size| read_mp4_sample_entry(reader, kind, size, info))?;
+            },
+            _ => ()
+        }
+        Ok(())
+    })
+}
+
+fn read_mp4_sample_entry<R: Read + Seek>(reader: &mut R, kind: &[u8; 4], size: u64, info: &mut MediaInfo)
+    -> io::Result<()>
+{
+    let (video, audio) = match kind {
+        b"avc1" | b"avc3" => (Some("H.264"), None),
+        b"hvc1" | b"hev1" => (Some("HEVC"), None),
+        b"dvh1" | b"dvhe" => (Some("HEVC"), None),
+        b"av01"           => (Some("AV1"), None),
+        b"vp09"           => (Some("VP9"), None),
+        b"mp4v"           => (Some("MPEG-4"), None),
+        b"mp4a"           => (None, Some("AAC")),
+        b"ac-3"           => (None, Some("AC3")),
+        b"ec-3"           => (None, Some("EAC3")),
+        b"Opus"           => (None, Some("Opus")),
+        b"fLaC"           => (None, Some("FLAC")),
+        b"alac"           => (None, Some("ALAC")),
+        _                 => (None, None)
+    };
+
+    if let Some(codec) = video.filter(|_| info.video_codec.is_none()) {
+        // The height follows the reserved and predefined fields of the visual sample entry.
+        reader.seek(SeekFrom::Current(26))?;
+        info.video_codec = Some(codec.to_owned());
+        info.video_height = Some(read_uint(reader, 2)?);
+        if kind == b"dvh1" || kind == b"dvhe" { info.hdr = Some("DV"); }
+
+        // The remainder of the visual sample entry precedes the boxes that describe the stream.
+        reader.seek(SeekFrom::Current(50))?;
+        for_each_box(reader, size.saturating_sub(78), |reader, kind, _| {
+            match kind {
+                b"colr" => {
+                    let mut colour_type = [0u8; 4];
+                    reader.read_exact(&mut colour_type)?;
+                    if &colour_type == b"nclx" {
+                        // The transfer characteristics follow the colour primaries.
+                        reader.seek(SeekFrom::Current(2))?;
+                        info.hdr = transfer_to_hdr(read_uint(reader, 2)?).or(info.hdr);
+                    }
+                },
+                b"dvcC" | b"dvvC" => info.hdr = Some("DV"),
+                _ => ()
+            }
+            Ok(())
+        })?;
+    } else if let Some(codec) = audio.filter(|_| info.audio_codec.is_none()) {
+        // The channel count follows the reserved fields of the audio sample entry.
+        reader.seek(SeekFrom::Current(16))?;
+        info.audio_codec = Some(codec.to_owned());
+        info.audio_channels = Some(read_uint(reader, 2)?);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn ebml(id: u64, data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let id_length = (8 - id.leading_zeros() as usize / 8).max(1);
+    output.extend_from_slice(&id.to_be_bytes()[8 - id_length..]);
+    output.push(0x01);
+    output.extend_from_slice(&(data.len() as u64).to_be_bytes()[1..]);
+    output.extend_from_slice(data);
+    output
+}
+
+#[test]
+fn test_read_matroska() {
+    use std::io::Cursor;
+
+    let video = [ebml(PIXEL_HEIGHT, &[0x04, 0x38]), ebml(COLOUR, &ebml(TRANSFER_CHARACTERISTICS, &[16]))].concat();
+    let video_track = [ebml(TRACK_TYPE, &[1]), ebml(CODEC_ID, b"V_MPEGH/ISO/HEVC"), ebml(VIDEO, &video)].concat();
+    let audio_track = [ebml(TRACK_TYPE, &[2]), ebml(CODEC_ID, b"A_EAC3"), ebml(AUDIO, &ebml(CHANNELS, &[6]))].concat();
+    let tracks = [ebml(TRACK_ENTRY, &video_track), ebml(TRACK_ENTRY, &audio_track)].concat();
+    let info = [ebml(TIMECODE_SCALE, &[0x0F, 0x42, 0x40]), ebml(DURATION, &2_520_000f64.to_bits().to_be_bytes())];
+    let segment = [ebml(INFO, &info.concat()), ebml(TRACKS, &tracks), ebml(CLUSTER, &[0; 16])].concat();
+    let file = [ebml(EBML_HEADER, &[0x42, 0x82, 0x84, b'w', b'e', b'b', b'm']), ebml(SEGMENT, &segment)].concat();
+
+    let info = read_matroska(&mut Cursor::new(file)).unwrap();
+    assert_eq!(info, MediaInfo {
+        video_height:   Some(1080),
+        video_codec:    Some("HEVC".to_owned()),
+        audio_codec:    Some("EAC3".to_owned()),
+        audio_channels: Some(6),
+        hdr:            Some("HDR10"),
+        duration:       Some(2520)
+    });
+    assert_eq!(info.channel_layout(), Some("5.1".to_owned()));
+}
+
+#[cfg(test)]
+fn mp4_box(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    [&((data.len() + 8) as u32).to_be_bytes()[..], &kind[..], data].concat()
+}
+
+#[test]
+fn test_read_mp4() {
+    use std::io::Cursor;
+
+    let mvhd = [&[0u8; 12][..], &600u32.to_be_bytes(), &(600u32 * 1500).to_be_bytes(), &[0; 80]].concat();
+    let visual = [&[0u8; 24][..], &1280u16.to_be_bytes(), &720u16.to_be_bytes(), &[0; 50]].concat();
+    let avc1 = mp4_box(b"avc1", &visual);
+    let audio = [&[0u8; 16][..], &2u16.to_be_bytes(), &[0; 10]].concat();
+    let mp4a = mp4_box(b"mp4a", &audio);
+    let stsd = |entry: &[u8]| mp4_box(b"stsd", &[&[0u8, 0, 0, 0, 0, 0, 0, 1][..], entry].concat());
+    let trak = |entry: &[u8]| mp4_box(b"trak", &mp4_box(b"mdia", &mp4_box(b"minf", &mp4_box(b"stbl", &stsd(entry)))));
+    let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", &mvhd), trak(&avc1), trak(&mp4a)].concat());
+    let file = [mp4_box(b"ftyp", b"isom\0\0\0\0"), moov, mp4_box(b"mdat", &[0; 16])].concat();
+
+    assert_eq!(read_mp4(&mut Cursor::new(file)).unwrap(), MediaInfo {
+        video_height:   Some(720),
+        video_codec:    Some("H.264".to_owned()),
+        audio_codec:    Some("AAC".to_owned()),
+        audio_channels: Some(2),
+        hdr:            None,
+        duration:       Some(1500)
+    });
+
+    // A sample description which is too small to hold its own header is reported, rather than panicking.
+    let file = [mp4_box(b"ftyp", b"isom\0\0\0\0"), mp4_box(b"moov", &mp4_box(b"stsd", &[0; 4]))].concat();
+    assert_eq!(read_mp4(&mut Cursor::new(file)).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    let huge = [&[0u8, 0, 0, 1][..], b"free", &u64::MAX.to_be_bytes()].concat();
+    let file = [mp4_box(b"ftyp", b"isom\0\0\0\0"), huge, vec![0; 8]].concat();
+    assert_eq!(read_mp4(&mut Cursor::new(file)).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,110 @@
+use tvdb;
+
+/// Series-level metadata, taken from the provider's record of the series rather than the name given by the user.
+/// Only the fields carried by TheTVDB's search results are available, which excludes the genre and status.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Series {
+    pub id:       u32,
+    pub name:     String,
+    pub year:     Option<u32>,
+    pub network:  Option<String>,
+    pub imdb_id:  Option<String>,
+}
+
+impl From<&tvdb::SeriesSearchResult> for Series {
+    fn from(result: &tvdb::SeriesSearchResult) -> Series {
+        Series {
+            id:      result.seriesid,
+            name:    result.seriesname.clone(),
+            year:    result.first_aired.as_ref().map(|date| date.year as u32),
+            network: result.network.clone(),
+            imdb_id: result.imdb_id.clone(),
+        }
+    }
+}
+
+/// The date that an episode or series first aired.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AirDate {
+    pub year:  u32,
+    pub month: u32,
+    pub day:   u32
+}
+
+impl AirDate {
+    /// The number of seconds since the Unix epoch at noon UTC on the date, so that file managers show the date
+    /// itself, rather than the day before or after, in every time zone within twelve hours of UTC.
+    pub fn timestamp(&self) -> i64 {
+        // Converts the civil date into days since the Unix epoch, from Howard Hinnant's `days_from_civil`.
+        let (month, day) = (self.month as i64, self.day as i64);
+        let year = self.year as i64 - if month <= 2 { 1 } else { 0 };
+        let era = year / 400;
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        (era * 146097 + day_of_era - 719468) * 86400 + 43200
+    }
+}
+
+impl From<&tvdb::Date> for AirDate {
+    fn from(date: &tvdb::Date) -> AirDate {
+        AirDate { year: date.year as u32, month: date.month as u32, day: date.day as u32 }
+    }
+}
+
+/// Episode-level metadata, taken from the provider's record of the episode.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Episode {
+    pub id:              u32,
+    pub title:           String,
+    pub first_aired:     Option<AirDate>,
+    pub production_code: Option<String>,
+    pub rating:          Option<String>,
+    pub director:        Option<String>,
+    pub writer:          Option<String>,
+}
+
+impl From<&tvdb::EpisodeInfo> for Episode {
+    fn from(info: &tvdb::EpisodeInfo) -> Episode {
+        Episode {
+            id:              info.id,
+            title:           info.episode_name.clone(),
+            first_aired:     info.first_aired.as_ref().map(AirDate::from),
+            production_code: info.production_code.clone().filter(|code| !code.is_empty()),
+            rating:          info.rating.as_ref().map(|rating| rating.to_string()),
+            director:        info.director.as_ref().and_then(|names| join_names(names)),
+            writer:          info.writer.as_ref().and_then(|names| join_names(names)),
+        }
+    }
+}
+
+/// TheTVDB separates lists of people with pipes, as in `|First Person|Second Person|`.
+fn join_names(names: &str) -> Option<String> {
+    let names: Vec<&str> = names.split('|').map(str::trim).filter(|name| !name.is_empty()).collect();
+    if names.is_empty() { None } else { Some(names.join(", ")) }
+}
+
+/// Searches TheTVDB for the given series name, and returns the first matching series.
+pub fn search_series(api: &tvdb::Tvdb, name: &str) -> Option<Series> {
+    api.search(name, "en").ok().and_then(|results| results.first().map(Series::from))
+}
+
+/// Obtains the metadata of an episode of the given series from TheTVDB.
+pub fn lookup_episode(api: &tvdb::Tvdb, series: &Series, season_no: u8, episode_no: u16) -> Option<Episode> {
+    api.episode(series.id, season_no as u32, episode_no as u32).ok().map(|info| Episode::from(&info))
+}
+
+#[test]
+fn test_air_date_timestamp() {
+    assert_eq!(AirDate { year: 1970, month: 1, day: 1 }.timestamp(), 43200);
+    assert_eq!(AirDate { year: 2010, month: 1, day: 14 }.timestamp(), 1263470400);
+    assert_eq!(AirDate { year: 2000, month: 2, day: 29 }.timestamp(), 951825600);
+    assert_eq!(AirDate { year: 2016, month: 12, day: 31 }.timestamp(), 1483185600);
+}
+
+#[test]
+fn test_join_names() {
+    assert_eq!(join_names("|First Person|Second Person|"), Some("First Person, Second Person".to_owned()));
+    assert_eq!(join_names("Only Person"), Some("Only Person".to_owned()));
+    assert_eq!(join_names("||"), None);
+}
@@ -1,16 +1,43 @@
 pub mod traits;
 pub mod tokenizer;
+pub mod config;
+pub mod sanitize;
+pub mod validate;
+pub mod release;
+pub mod media;
+pub mod parts;
+pub mod plan;
+pub mod preflight;
+pub mod metadata;
+pub mod checksum;
+pub mod journal;
+pub mod sonarr;
+pub mod render;
+pub mod rewrite;
+pub mod rules;
+pub mod transfer;
 mod mimetypes;
 
 use std::env;
 use std::fs::{self, ReadDir};
-use std::io::Error;
+use std::io::{self, Error};
 use std::path::{Path, PathBuf};
 
+use regex::Regex;
 use tvdb;
 
+use self::media::MediaInfo;
+use self::metadata::{AirDate, Episode, Series};
+use self::parts::EpisodeFile;
+use self::plan::ConflictPolicy;
+use self::release::Release;
+use self::render::TemplateValues;
+use self::rewrite::Rewrite;
+use self::rules::Rule;
+use self::sanitize::Policy;
 use self::tokenizer::TemplateToken as Token;
-use self::traits::Digits;
+use self::transfer::TransferMode;
+use self::validate::UnsafeTarget;
 
 macro_rules! lowercase {
     ($path:ident) => { $path.to_string_lossy().to_lowercase() }
@@ -18,21 +45,48 @@ macro_rules! lowercase {
 
 pub const DRY_RUN: u8 = 1;
 pub const VERBOSE: u8 = 2;
+pub const REMOVE_EMPTY: u8 = 4;
+pub const VERIFY: u8 = 8;
+pub const KEEP_GOING: u8 = 16;
+pub const AIR_DATE_MTIME: u8 = 32;
+pub const EPISODE_START: u8 = 64;
 
 pub struct Arguments {
-    pub flags:          u8,
-    pub season_index:   u8,
-    pub pad_length:     u8,
-    pub episode_index:  u16,
-    pub base_directory: String,
-    pub series_name:    String,
-    pub template:       Vec<Token>
+    pub flags:            u8,
+    pub season_index:     u8,
+    pub pad_length:       u8,
+    pub episode_index:    u16,
+    pub base_directory:   String,
+    pub output_directory: Option<PathBuf>,
+    pub series_name:      String,
+    pub template:         Vec<Token>,
+    pub sanitize:         Policy,
+    pub name_limit:       usize,
+    pub season_template:  Option<Vec<Token>>,
+    pub series_template:  Option<Vec<Token>>,
+    pub rules:            Vec<Rule>,
+    pub rewrites:         Vec<Rewrite>,
+    pub on_conflict:      ConflictPolicy,
+    pub transfer:         TransferMode
 }
 
 #[derive(Debug)]
 pub struct Season {
     pub season_no: u8,
-    pub episodes:  Vec<PathBuf>
+    pub directory: PathBuf,
+    pub episodes:  Vec<EpisodeFile>
+}
+
+impl Season {
+    /// Pairs each episode file with its episode number in alphabetical order, counting from `episode_no`. Each
+    /// part of an episode which is split across multiple files shares the number of the first part.
+    pub fn numbered_episodes(&self, episode_no: u16) -> Vec<(&EpisodeFile, u16)> {
+        let mut next_no = episode_no;
+        self.episodes.iter().map(|file| {
+            if !file.continues_episode() || next_no == episode_no { next_no += 1; }
+            (file, next_no - 1)
+        }).collect()
+    }
 }
 
 /// Takes a pathname and shortens it for readability.
@@ -52,6 +106,18 @@ pub enum ScanDir {
     Seasons(Vec<Season>)
 }
 
+impl ScanDir {
+    /// Collects every episode that was found, regardless of which season it belongs to.
+    pub fn episodes(&self) -> Vec<&PathBuf> {
+        match *self {
+            ScanDir::Episodes(ref season) => season.episodes.iter().map(|file| &file.path).collect(),
+            ScanDir::Seasons(ref seasons) => seasons.iter()
+                .flat_map(|season| season.episodes.iter().map(|file| &file.path))
+                .collect()
+        }
+    }
+}
+
 quick_error! {
     #[derive(Debug)]
     pub enum ReadDirError {
@@ -84,49 +150,214 @@ pub fn scan_directory<P: AsRef<Path>>(directory: P, season_no: u8) -> Result<Sca
     }
 
     // If the directory does not contain season directories, return a list of episodes.
-    get_episodes(directory, season_no).map(ScanDir::Episodes)
+    get_episodes(directory, season_no, &parts::marker_pattern()).map(ScanDir::Episodes)
+}
+
+/// The path that an episode will be renamed to.
+#[derive(Debug)]
+pub struct Target {
+    pub path:          PathBuf,
+    /// The original and rewritten title of the episode, if a rewrite changed the title.
+    pub title_rewrite: Option<(String, String)>,
+    /// The date that the episode first aired, if it is known.
+    pub air_date:      Option<AirDate>
 }
 
 pub enum TargetErr {
     EpisodeDoesNotExist,
     Extension,
-    Parent
+    Parent,
+    NameTooLong,
+    Unsafe(UnsafeTarget),
+    Unreadable(Error)
 }
 
-/// Target requires source path, template tokens, episode number, and name of TV series
-pub fn collect_target(source: &Path, season_no: u8, episode_no: u16, arguments: &Arguments,
-    tvdb_api: &tvdb::Tvdb, tvdb_series_id: u32)-> Result<PathBuf, TargetErr>
+/// Selects the template of the first routing rule that applies to the source, or else the default template.
+pub fn select_template<'a>(source: &Path, season_no: u8, arguments: &'a Arguments) -> &'a [Token] {
+    rules::select(&arguments.rules, source, season_no).unwrap_or(&arguments.template)
+}
+
+/// Target requires source path, template tokens, episode number, and name of TV series.
+///
+/// Any `/` characters within the template are treated as path separators, so that a template may describe a
+/// directory structure, such as `${Series}/Season ${Season}/${Series} - ${Season}x${Episode}`. The resulting path
+/// is relative to the output directory if one was given, or else the directory of the source, and is validated
+/// to ensure that it remains within that directory.
+///
+/// If the episode is split across multiple files, the part number is substituted into the `${Part}` token, or
+/// appended to the name as ` - pt1` if the template does not contain that token.
+pub fn collect_target(source: &Path, season_no: u8, episode_no: u16, part: Option<u8>, arguments: &Arguments,
+    tvdb_api: &tvdb::Tvdb, series: &Series)-> Result<Target, TargetErr>
 {
-    let episode = tvdb_api.episode(tvdb_series_id, season_no as u32, episode_no as u32)
-        .map_err(|_| TargetErr::EpisodeDoesNotExist)?;
-
-    let mut filename = String::with_capacity(64);
-    for pattern in &arguments.template {
-        match *pattern {
-            Token::Character(value) => filename.push(value),
-            Token::Series           => filename.push_str(&arguments.series_name),
-            Token::Season           => filename.push_str(&season_no.to_string()),
-            Token::Episode          => filename.push_str(&episode_no.to_padded_string('0', arguments.pad_length as usize)),
-            Token::TvdbTitle        => filename.push_str(&episode.episode_name),
-            Token::TvdbFirstAired   => if let Some(date) = episode.first_aired.clone() {
-                filename.push_str(&date.year.to_string());
-                filename.push('-');
-                filename.push_str(&date.month.to_padded_string('0', 2));
-                filename.push('-');
-                filename.push_str(&date.day.to_padded_string('0', 2));
-            }
-        }
-    }
+    let mut episode = metadata::lookup_episode(tvdb_api, series, season_no, episode_no)
+        .ok_or(TargetErr::EpisodeDoesNotExist)?;
+
+    // Metadata values are rewritten by the user's rewrite rules before they are templated.
+    let names = [arguments.series_name.as_str(), series.name.as_str()];
+    let apply = |value: &str| rewrite::rewrite(&arguments.rewrites, &names, value);
+    let original_title = episode.title.clone();
+    episode.title = apply(&episode.title);
+    episode.director = episode.director.as_ref().map(|value| apply(value));
+    episode.writer = episode.writer.as_ref().map(|value| apply(value));
+    let series = Series {
+        name:    apply(&series.name),
+        network: series.network.as_ref().map(|value| apply(value)),
+        ..series.clone()
+    };
+    let template = select_template(source, season_no, arguments);
+
+    // Information about the release is derived from the source's original file name.
+    let release = release::parse(&source.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default());
+
+    // Technical information is only read from the media file's headers when the template requires it. The runtime
+    // is also probed from the headers, but is left empty rather than failing when they are unreadable.
+    let media = if template.iter().any(Token::requires_media) {
+        media::read_media_info(source).map_err(TargetErr::Unreadable)?
+    } else if template.iter().any(|token| *token.value() == Token::Runtime) {
+        media::read_media_info(source).unwrap_or_default()
+    } else {
+        MediaInfo::default()
+    };
+
+    // The checksum requires reading the entire file, so it is only computed when the template requires it.
+    let crc32 = if template.iter().any(|token| *token.value() == Token::Crc32) {
+        Some(checksum::crc32_file(source).map_err(TargetErr::Unreadable)?)
+    } else {
+        None
+    };
+
+    // Parts of the same episode would otherwise be given the same name.
+    let suffix = part.filter(|_| !template.iter().any(|token| *token.value() == Token::Part))
+        .map_or(String::new(), |part| format!(" - pt{}", part));
 
     let extension = source.extension()
         .and_then(|s| s.to_str())
         .ok_or(TargetErr::Extension)?;
 
-    filename = [&filename.trim().replace("/", "-"), ".", &extension].concat();
+    // Each component of the path is sanitized, and empty directory components are discarded. The last component is
+    // the name of the file, along with the part suffix and the extension.
+    let render_path = |title: &str| {
+        let shortened = Episode { title: title.to_owned(), ..episode.clone() };
+        let rendered = render::render(template, &TemplateValues {
+            season_no:   season_no,
+            episode_no:  episode_no,
+            pad_length:  arguments.pad_length,
+            series_name: &arguments.series_name,
+            series:      &series,
+            episode:     &shortened,
+            release:     &release,
+            media:       &media,
+            crc32:       crc32,
+            part:        part
+        });
+        let mut components: Vec<String> = rendered.split('/')
+            .map(|component| sanitize::sanitize_component(component, arguments.sanitize))
+            .collect();
+        let stem = components.pop().unwrap_or_default();
+        components.retain(|component| !component.is_empty());
+        let filename = if stem.is_empty() { stem } else { [stem.as_str(), &suffix, ".", extension].concat() };
+        (components, filename)
+    };
+
+    // Only the title is shortened to fit the name limit, so the rest of the name, such as the episode number or
+    // checksum, is kept intact.
+    let title = sanitize::fit_title(&episode.title, arguments.name_limit, |title| render_path(title).1)
+        .ok_or(TargetErr::NameTooLong)?;
+    let (components, filename) = render_path(title);
+    if filename.is_empty() { return Err(TargetErr::Parent) }
+    let components = components.iter().map(|component| sanitize::truncate(component, arguments.name_limit));
+
+    let root = match arguments.output_directory {
+        Some(ref directory) => directory.clone(),
+        None => source.parent().map(PathBuf::from).ok_or(TargetErr::Parent)?
+    };
+
+    let target = components.fold(root.clone(), |path, component| path.join(component)).join(filename);
+    validate::validate_target(&root, &target).map_err(TargetErr::Unsafe)?;
+    Ok(Target {
+        path:          target,
+        title_rewrite: if original_title != episode.title { Some((original_title, episode.title)) } else { None },
+        air_date:      episode.first_aired
+    })
+}
+
+/// The name that is given to the directory of season 0, in place of the season template.
+pub const SPECIALS: &'static str = "Specials";
+
+/// Computes the new path of a season directory from the season template, such as `Season ${Season:02}`. Season 0
+/// is always named `Specials`.
+pub fn season_directory_target(season: &Season, template: &[Token], arguments: &Arguments, series: &Series)
+    -> Result<PathBuf, TargetErr>
+{
+    if season.season_no == 0 {
+        directory_target(&season.directory, &[], SPECIALS, 0, arguments, series)
+    } else {
+        directory_target(&season.directory, template, "", season.season_no, arguments, series)
+    }
+}
+
+/// Computes the new path of a series directory from the series template, such as `${Series} (${Series_Year})`.
+pub fn series_directory_target(directory: &Path, template: &[Token], arguments: &Arguments, series: &Series)
+    -> Result<PathBuf, TargetErr>
+{
+    directory_target(directory, template, "", 0, arguments, series)
+}
+
+/// Renders a template as the new name of a directory, which is kept within the same parent directory. Only the
+/// series metadata and season number are available to a directory's template; episode tokens will be empty.
+fn directory_target(directory: &Path, template: &[Token], prefix: &str, season_no: u8, arguments: &Arguments,
+    series: &Series) -> Result<PathBuf, TargetErr>
+{
+    let name = [prefix, &render::render(template, &TemplateValues {
+        season_no:   season_no,
+        episode_no:  0,
+        pad_length:  arguments.pad_length,
+        series_name: &arguments.series_name,
+        series:      series,
+        episode:     &Episode::default(),
+        release:     &Release::default(),
+        media:       &MediaInfo::default(),
+        crc32:       None,
+        part:        None
+    })].concat();
+
+    let name = sanitize::sanitize_component(&name, arguments.sanitize);
+    if name.is_empty() { return Err(TargetErr::Parent) }
+
+    let parent = directory.parent().ok_or(TargetErr::Parent)?;
+    let target = parent.join(sanitize::truncate(&name, arguments.name_limit));
+    validate::validate_target(parent, &target).map_err(TargetErr::Unsafe)?;
+    Ok(target)
+}
+
+/// Determines the season and episode numbers of a source. If the source was previously renamed with the same
+/// template, the numbers are recovered from its name, so that renaming a directory twice is idempotent. Otherwise,
+/// the season number of the directory and the next episode number in alphabetical order are used. When the starting
+/// episode was given explicitly, the positional numbers are always used.
+pub fn episode_numbers(source: &Path, template: &[Token], season_no: u8, episode_no: u16, explicit: bool) -> (u8, u16) {
+    if explicit { return (season_no, episode_no) }
+    source.file_stem()
+        .and_then(|stem| tokenizer::parse_filename(template, &stem.to_string_lossy()))
+        .map_or((season_no, episode_no), |(season, episode)| (season.unwrap_or(season_no), episode))
+}
 
-    source.parent()
-        .map(|parent| PathBuf::from(parent).join(filename))
-        .ok_or(TargetErr::Parent)
+/// Removes the directories which were left empty by moving the given sources away, walking upward from the parent
+/// of each source and stopping before the root. Directories which still contain files, which were already empty
+/// before the run, or which are symbolic links, are left untouched.
+pub fn remove_empty_directories<'a, I>(root: &Path, sources: I) -> io::Result<()>
+    where I: IntoIterator<Item = &'a PathBuf>
+{
+    for source in sources {
+        let mut directory = source.parent();
+        while let Some(current) = directory {
+            if current == root || !current.starts_with(root) { break }
+            let is_directory = fs::symlink_metadata(current).map(|metadata| metadata.file_type().is_dir());
+            if !is_directory.unwrap_or(false) || fs::read_dir(current)?.next().is_some() { break }
+            fs::remove_dir(current)?;
+            directory = current.parent();
+        }
+    }
+    Ok(())
 }
 
 fn read_directory(directory: &Path) -> Result<ReadDir, ReadDirError> {
@@ -137,13 +368,14 @@ fn read_directory(directory: &Path) -> Result<ReadDir, ReadDirError> {
 fn get_seasons<P: AsRef<Path>>(directory: P) -> Result<Vec<Season>, ReadDirError> {
     let directory: &Path = directory.as_ref();
     let mut output: Vec<Season> = Vec::new();
+    let pattern = parts::marker_pattern();
 
     for entry in read_directory(directory)? {
         let entry = entry.map_err(|_| ReadDirError::InvalidDirEntry(directory.to_path_buf()))?;
         let season = entry.path();
         if season.is_dir() {
             if let Some(number) = derive_season_number(&season) {
-                output.push(get_episodes(&season, number)?);
+                output.push(get_episodes(&season, number, &pattern)?);
             }
         }
     }
@@ -154,7 +386,7 @@ fn get_seasons<P: AsRef<Path>>(directory: P) -> Result<Vec<Season>, ReadDirError
 }
 
 /// Collects a list of all of the episodes in a given directory. Files that are not videos are ignored.
-fn get_episodes<P: AsRef<Path>>(directory: P, season_no: u8) -> Result<Season, ReadDirError> {
+fn get_episodes<P: AsRef<Path>>(directory: P, season_no: u8, pattern: &Regex) -> Result<Season, ReadDirError> {
     let directory: &Path = directory.as_ref();
     // Collect a list of video extensions
     let video_extensions = mimetypes::get_extensions("video").map_err(ReadDirError::MimeError)?;
@@ -204,7 +436,7 @@ fn get_episodes<P: AsRef<Path>>(directory: P, season_no: u8) -> Result<Season, R
     episodes.sort_by(|a, b| lowercase!(a).cmp(&lowercase!(b)));
 
     // Return the list of episodes as a `Season` with the accompanying season number.
-    Ok(Season { season_no: season_no, episodes: episodes })
+    Ok(Season { season_no: season_no, directory: directory.to_path_buf(), episodes: parts::assign_parts(pattern, episodes) })
 }
 
 /// Given a directory path, derive the number of the season and assign it.
@@ -221,6 +453,37 @@ pub fn derive_season_number(season: &Path) -> Option<u8> {
         })
 }
 
+/// A directory for a test beneath the system's temporary directory, which is removed along with its contents when
+/// it is dropped, so that a failing test does not leave it behind.
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("tv-renamer-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+#[cfg(test)]
+impl ::std::ops::Deref for TempDir {
+    type Target = Path;
+    fn deref(&self) -> &Path { &self.0 }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path { &self.0 }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) { let _ = fs::remove_dir_all(&self.0); }
+}
+
 #[test]
 fn test_derive_season_number() {
     assert_eq!(derive_season_number(&Path::new("Specials")), Some(0));
@@ -229,3 +492,43 @@ fn test_derive_season_number() {
     assert_eq!(derive_season_number(&Path::new("season9")), Some(9));
     assert_eq!(derive_season_number(&Path::new("Extras")), None);
 }
+
+#[cfg(unix)]
+#[test]
+fn test_remove_empty_directories() {
+    use std::os::unix::fs::symlink;
+
+    let root = TempDir::new("empty");
+    let outside = TempDir::new("outside");
+    fs::create_dir_all(root.join("Season 1/Extras")).unwrap();
+    fs::create_dir_all(root.join("Untouched")).unwrap();
+    symlink(&outside, root.join("Linked")).unwrap();
+
+    // The episode within `Season 1/Extras` has been moved away, and the symbolic link points to an empty directory.
+    let sources = [root.join("Season 1/Extras/ep01.mkv"), root.join("Linked/ep02.mkv")];
+    remove_empty_directories(&root, &sources).unwrap();
+    assert!(!root.join("Season 1").exists());
+    assert!(root.join("Untouched").is_dir() && root.join("Linked").exists() && outside.is_dir() && root.is_dir());
+}
+
+#[test]
+fn test_episode_numbers() {
+    let template = tokenizer::tokenize_template("${Series} ${Season}x${Episode}");
+    let source = Path::new("Season 1/Show 2x05.mkv");
+    assert_eq!(episode_numbers(source, &template, 1, 3, false), (2, 5));
+    assert_eq!(episode_numbers(source, &template, 1, 3, true), (1, 3));
+    assert_eq!(episode_numbers(Path::new("Season 1/ep03.mkv"), &template, 1, 3, false), (1, 3));
+}
+
+#[test]
+fn test_numbered_episodes() {
+    let season = Season {
+        season_no: 1,
+        directory: PathBuf::from("Season 1"),
+        episodes:  parts::assign_parts(&parts::marker_pattern(), vec![
+            PathBuf::from("ep01.cd1.avi"), PathBuf::from("ep01.cd2.avi"), PathBuf::from("ep02.avi")
+        ])
+    };
+    let numbers: Vec<u16> = season.numbered_episodes(1).into_iter().map(|(_, episode_no)| episode_no).collect();
+    assert_eq!(numbers, vec![1, 1, 2]);
+}
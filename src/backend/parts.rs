@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use regex::Regex;
+
+/// A video file within a season, along with its part number if the episode is split across multiple files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpisodeFile {
+    pub path: PathBuf,
+    pub part: Option<u8>
+}
+
+impl EpisodeFile {
+    /// Every part after the first continues the episode of the part before it.
+    pub fn continues_episode(&self) -> bool {
+        self.part.is_some_and(|part| part > 1)
+    }
+}
+
+/// Matches part markers such as `cd1`, `disc 2`, `part1`, or `pt.2`. The pattern is compiled once for each scan of
+/// a directory, and is shared by all of its seasons.
+pub fn marker_pattern() -> Regex {
+    Regex::new(r"(?i)\b(cd|dis[ck]|part|pt)[ ._-]?(\d{1,2})\b").unwrap()
+}
+
+/// Finds the last part marker within a file stem, such as `cd1`, `disc 2`, `part1`, or `pt.2`, and returns the
+/// stem with the marker removed, along with the part number.
+fn part_marker(pattern: &Regex, stem: &str) -> Option<(String, u8)> {
+    pattern.captures_iter(stem).last().and_then(|captures| {
+        let marker = captures.get(0)?;
+        let part = captures.get(2)?.as_str().parse::<u8>().ok()?;
+        Some(([&stem[..marker.start()], &stem[marker.end()..]].concat(), part))
+    })
+}
+
+/// Assigns part numbers to a sorted list of files. A file is only considered to be a part of an episode if every
+/// part before it is also in the list under the same name apart from its part marker, and there are at least two
+/// parts. Titles such as `Finale Part 1` are therefore not mistaken for split files when each part is a separate
+/// episode, and an orphaned `cd2` is not attached to an unrelated episode.
+pub fn assign_parts(pattern: &Regex, paths: Vec<PathBuf>) -> Vec<EpisodeFile> {
+    let markers: Vec<Option<(String, u8)>> = paths.iter()
+        .map(|path| path.file_stem().and_then(|stem| part_marker(pattern, &stem.to_string_lossy())))
+        .collect();
+    let has_part = |key: &str, part: u8| {
+        markers.iter().flatten().any(|(other, number)| other == key && *number == part)
+    };
+
+    paths.into_iter().zip(markers.iter()).map(|(path, marker)| {
+        let part = marker.as_ref().and_then(|(key, part)| {
+            // The number of consecutive parts, starting from the first, which are present for this name.
+            let parts = (1..=u8::MAX).take_while(|&number| has_part(key, number)).count();
+            if parts >= 2 && (1..=parts).contains(&(*part as usize)) { Some(*part) } else { None }
+        });
+        EpisodeFile { path: path, part: part }
+    }).collect()
+}
+
+#[test]
+fn test_assign_parts() {
+    let files = assign_parts(&marker_pattern(), vec![
+        PathBuf::from("ep01.cd1.avi"),
+        PathBuf::from("ep01.cd2.avi"),
+        PathBuf::from("ep02.avi"),
+        PathBuf::from("ep03.cd2.avi"),
+        PathBuf::from("ep04.cd1.avi"),
+        PathBuf::from("ep04.cd3.avi"),
+        PathBuf::from("Show S01E03 Finale Part 1.avi"),
+        PathBuf::from("Show S01E04 Finale Part 2.avi"),
+        PathBuf::from("Show - pt1.mkv"),
+        PathBuf::from("Show - pt2.mkv"),
+        PathBuf::from("Show - pt3.mkv"),
+    ]);
+    let parts: Vec<Option<u8>> = files.iter().map(|file| file.part).collect();
+    assert_eq!(parts, vec![Some(1), Some(2), None, None, None, None, None, None, Some(1), Some(2), Some(3)]);
+    assert!(files[1].continues_episode() && !files[0].continues_episode());
+}
@@ -0,0 +1,673 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use tvdb;
+
+use super::{Arguments, Season, Target, TargetErr, AIR_DATE_MTIME, EPISODE_START};
+use super::metadata::{AirDate, Series};
+use super::release;
+use super::transfer::{self, TransferMode};
+#[cfg(test)]
+use super::TempDir;
+
+/// A single episode which is to be renamed from its source to its target.
+#[derive(Debug)]
+pub struct Rename {
+    pub source:        PathBuf,
+    pub target:        PathBuf,
+    /// The original and rewritten title of the episode, if a rewrite changed the title.
+    pub title_rewrite: Option<(String, String)>,
+    /// Whether the file which already exists at the target may be replaced.
+    pub overwrite:     bool,
+    /// The date that the episode first aired, which becomes the modification time of the target when given.
+    pub air_date:      Option<AirDate>
+}
+
+impl Rename {
+    /// Episodes which were previously renamed and are still named correctly do not need to be renamed.
+    pub fn is_unchanged(&self) -> bool { self.source == self.target }
+
+    /// A rename of the source to the target without a title rewrite, overwrite, or air date.
+    #[cfg(test)]
+    pub fn new<P: Into<PathBuf>, Q: Into<PathBuf>>(source: P, target: Q) -> Rename {
+        Rename { source: source.into(), target: target.into(), title_rewrite: None, overwrite: false, air_date: None }
+    }
+}
+
+/// Describes why the target of an episode could not be computed.
+pub struct PlanError {
+    pub source:     PathBuf,
+    pub episode_no: u16,
+    pub why:        TargetErr
+}
+
+/// A problem with the plan as a whole, which is found before any file is renamed.
+#[derive(Debug, PartialEq)]
+pub enum Conflict {
+    /// A file already exists at the target of a rename.
+    TargetExists(PathBuf),
+    /// More than one episode would be renamed to the same target.
+    DuplicateTarget(PathBuf)
+}
+
+/// Determines how a rename is handled when a file already exists at its target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictPolicy {
+    /// The episode is left as it is, and reported as skipped.
+    Skip,
+    /// The existing file is replaced by the episode.
+    Overwrite,
+    /// The episode is renamed to the first free name with a ` (2)`-style suffix.
+    Suffix,
+    /// Nothing within the season is renamed.
+    Abort,
+    /// The frontend asks what to do for each conflict.
+    Ask
+}
+
+impl ConflictPolicy {
+    pub fn from_name(name: &str) -> Option<ConflictPolicy> {
+        match name {
+            "skip"      => Some(ConflictPolicy::Skip),
+            "overwrite" => Some(ConflictPolicy::Overwrite),
+            "suffix"    => Some(ConflictPolicy::Suffix),
+            "abort"     => Some(ConflictPolicy::Abort),
+            "ask"       => Some(ConflictPolicy::Ask),
+            _           => None
+        }
+    }
+}
+
+/// The action that is taken for a single rename whose target already exists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    Skip,
+    Overwrite,
+    Suffix,
+    Abort
+}
+
+/// One of several episodes which would be renamed to the same target.
+#[derive(Debug)]
+pub struct Candidate {
+    pub source:  PathBuf,
+    pub size:    Option<u64>,
+    /// The quality of the release, as described by its file name.
+    pub quality: Option<String>
+}
+
+impl Candidate {
+    fn new(source: &Path) -> Candidate {
+        let stem = source.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        Candidate {
+            source:  source.to_path_buf(),
+            size:    fs::metadata(source).ok().map(|metadata| metadata.len()),
+            quality: release::parse(&stem).quality()
+        }
+    }
+
+    /// Describes the candidate by its file name, size, and quality, such as `one.mkv (1.4 GiB, 1080p BluRay)`.
+    pub fn describe(&self) -> String {
+        let name = self.source.file_name().unwrap_or_else(|| self.source.as_os_str()).to_string_lossy();
+        let mut details = Vec::new();
+        if let Some(size) = self.size { details.push(format_size(size)); }
+        if let Some(ref quality) = self.quality { details.push(quality.clone()); }
+        if details.is_empty() { name.into_owned() } else { format!("{} ({})", name, details.join(", ")) }
+    }
+}
+
+/// Formats a number of bytes with a binary unit, such as `1.4 GiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&'static str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", size, UNITS[unit]) }
+}
+
+/// Several episodes which would be renamed to the same target, such as two releases of the same episode.
+#[derive(Debug)]
+pub struct Duplicate {
+    pub target:     PathBuf,
+    pub candidates: Vec<Candidate>
+}
+
+/// The action that is taken for several episodes which would be renamed to the same target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateResolution {
+    /// Every candidate is kept, and all but one are given a ` (2)`-style suffix.
+    Suffix,
+    /// Only the candidate at the given index is renamed, and the others are skipped.
+    Keep(usize),
+    Abort
+}
+
+/// Describes the rename that failed while a plan was being applied, and the outcome of reverting the renames
+/// that had already been performed.
+#[derive(Debug)]
+pub struct ApplyError {
+    pub source:            PathBuf,
+    pub target:            PathBuf,
+    pub why:               io::Error,
+    /// The number of renames which were reverted.
+    pub reverted:          usize,
+    /// The renames which could not be reverted, as their source, target, and the reason for the failure.
+    pub rollback_failures: Vec<(PathBuf, PathBuf, io::Error)>
+}
+
+impl ApplyError {
+    /// Whether every rename that was performed before the failure has been reverted.
+    pub fn rolled_back(&self) -> bool { self.rollback_failures.is_empty() }
+}
+
+/// Every rename within a season, which is computed in full before any changes are made to the filesystem, so
+/// that a failure to look up an episode does not leave the season partially renamed.
+#[derive(Debug)]
+pub struct RenamePlan {
+    pub renames:  Vec<Rename>,
+    /// Renames which were removed from the plan because their targets already exist.
+    pub skipped:  Vec<Rename>,
+    pub transfer: TransferMode
+}
+
+impl RenamePlan {
+    /// Computes the target of every episode within the season, counting from the given episode number.
+    pub fn for_season(season: &Season, episode_no: u16, arguments: &Arguments, api: &tvdb::Tvdb, series: &Series)
+        -> Result<RenamePlan, PlanError>
+    {
+        RenamePlan::collect(season, episode_no, arguments, api, series, false).map(|(plan, _)| plan)
+    }
+
+    /// Computes the target of every episode within the season, counting from the given episode number. Episodes
+    /// whose targets could not be computed are left out of the plan, and are returned along with the plan.
+    pub fn for_season_partial(season: &Season, episode_no: u16, arguments: &Arguments, api: &tvdb::Tvdb,
+        series: &Series) -> (RenamePlan, Vec<PlanError>)
+    {
+        RenamePlan::collect(season, episode_no, arguments, api, series, true).unwrap_or_else(|why| {
+            (RenamePlan { renames: Vec::new(), skipped: Vec::new(), transfer: arguments.transfer }, vec![why])
+        })
+    }
+
+    /// Computes the target of every episode, either stopping at the first error, or collecting every error.
+    fn collect(season: &Season, episode_no: u16, arguments: &Arguments, api: &tvdb::Tvdb, series: &Series,
+        partial: bool) -> Result<(RenamePlan, Vec<PlanError>), PlanError>
+    {
+        let mut renames = Vec::with_capacity(season.episodes.len());
+        let mut errors = Vec::new();
+        for (file, episode_no) in season.numbered_episodes(episode_no) {
+            let source = &file.path;
+            let template = super::select_template(source, season.season_no, arguments);
+            let (season_no, current_no) = super::episode_numbers(source, template, season.season_no, episode_no,
+                arguments.flags & EPISODE_START != 0);
+
+            match super::collect_target(source, season_no, current_no, file.part, arguments, api, series) {
+                Ok(Target { path, title_rewrite, air_date }) => renames.push(Rename {
+                    source:        source.clone(),
+                    target:        path,
+                    title_rewrite: title_rewrite,
+                    overwrite:     false,
+                    air_date:      air_date.filter(|_| arguments.flags & AIR_DATE_MTIME != 0)
+                }),
+                Err(why) => {
+                    let error = PlanError { source: source.clone(), episode_no: current_no, why: why };
+                    if !partial { return Err(error) }
+                    errors.push(error);
+                }
+            }
+        }
+        Ok((RenamePlan { renames: renames, skipped: Vec::new(), transfer: arguments.transfer }, errors))
+    }
+
+    /// Whether the file at the path will be moved elsewhere by another rename within the plan, as in a chain or a
+    /// swap, so that the path will be free by the time that it is needed. Transfers which keep their sources never
+    /// free a path.
+    fn vacates(&self, path: &Path) -> bool {
+        self.transfer.removes_source()
+            && self.renames.iter().any(|rename| !rename.is_unchanged() && rename.source == path)
+    }
+
+    /// Whether a file, other than one which is being moved away by the plan, already exists at the target.
+    fn target_exists(&self, rename: &Rename) -> bool {
+        !rename.overwrite && rename.target.exists() && !self.vacates(&rename.target)
+    }
+
+    /// The first name of the form `Target (2).ext` which neither exists nor is the target of another rename.
+    fn suffixed_target(&self, target: &Path) -> PathBuf {
+        let stem = target.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        let extension = target.extension().map(|extension| extension.to_string_lossy().into_owned());
+        (2..).map(|number| match extension {
+                Some(ref extension) => target.with_file_name(format!("{} ({}).{}", stem, number, extension)),
+                None                => target.with_file_name(format!("{} ({})", stem, number))
+            })
+            .find(|candidate| !candidate.exists() && !self.renames.iter().any(|rename| &rename.target == candidate))
+            .unwrap()
+    }
+
+    /// Resolves every rename whose target already exists according to the policy. When the policy is `Ask`, the
+    /// resolution for each conflict is obtained from `ask`. Skipped renames are moved into `skipped`. If a
+    /// conflict is to be aborted, the target of that rename is returned.
+    pub fn resolve_conflicts<F>(&mut self, policy: ConflictPolicy, mut ask: F) -> Result<(), PathBuf>
+        where F: FnMut(&Rename) -> Resolution
+    {
+        let mut index = 0;
+        while index < self.renames.len() {
+            if self.renames[index].is_unchanged() || !self.target_exists(&self.renames[index]) {
+                index += 1;
+                continue
+            }
+
+            let resolution = match policy {
+                ConflictPolicy::Skip      => Resolution::Skip,
+                ConflictPolicy::Overwrite => Resolution::Overwrite,
+                ConflictPolicy::Suffix    => Resolution::Suffix,
+                ConflictPolicy::Abort     => Resolution::Abort,
+                ConflictPolicy::Ask       => ask(&self.renames[index])
+            };
+
+            match resolution {
+                Resolution::Skip => {
+                    let rename = self.renames.remove(index);
+                    self.skipped.push(rename);
+                    continue
+                },
+                Resolution::Overwrite => self.renames[index].overwrite = true,
+                Resolution::Suffix => {
+                    let target = self.suffixed_target(&self.renames[index].target);
+                    self.renames[index].target = target;
+                },
+                Resolution::Abort => return Err(self.renames[index].target.clone())
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+
+    /// Finds every set of episodes within the plan which would be renamed to the same target, and resolves each set
+    /// according to the policy. When the policy is `Ask`, the resolution for each set is obtained from `choose`.
+    /// The `skip` policy keeps the first candidate, and the `overwrite` policy aborts, as one episode would replace
+    /// another. Skipped renames are moved into `skipped`. If a set is to be aborted, its target is returned.
+    pub fn resolve_duplicates<F>(&mut self, policy: ConflictPolicy, mut choose: F) -> Result<(), PathBuf>
+        where F: FnMut(&Duplicate) -> DuplicateResolution
+    {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (index, rename) in self.renames.iter().enumerate() {
+            match groups.iter_mut().find(|group| self.renames[group[0]].target == rename.target) {
+                Some(group) => group.push(index),
+                None => groups.push(vec![index])
+            }
+        }
+
+        let mut skipped = Vec::new();
+        for group in groups.into_iter().filter(|group| group.len() > 1) {
+            let duplicate = Duplicate {
+                target:     self.renames[group[0]].target.clone(),
+                candidates: group.iter().map(|&index| Candidate::new(&self.renames[index].source)).collect()
+            };
+
+            let resolution = match policy {
+                ConflictPolicy::Suffix                            => DuplicateResolution::Suffix,
+                ConflictPolicy::Skip                              => DuplicateResolution::Keep(0),
+                ConflictPolicy::Overwrite | ConflictPolicy::Abort => DuplicateResolution::Abort,
+                ConflictPolicy::Ask                               => choose(&duplicate)
+            };
+
+            match resolution {
+                DuplicateResolution::Suffix => {
+                    // An episode which is already named correctly keeps its name.
+                    let keeper = group.iter().cloned()
+                        .find(|&index| self.renames[index].is_unchanged())
+                        .unwrap_or(group[0]);
+                    for &index in group.iter().filter(|&&index| index != keeper) {
+                        let target = self.suffixed_target(&duplicate.target);
+                        self.renames[index].target = target;
+                    }
+                },
+                // Episodes which are already named correctly are left as they are, rather than being skipped.
+                DuplicateResolution::Keep(kept) => skipped.extend(group.iter().enumerate()
+                    .filter(|&(position, &index)| position != kept && !self.renames[index].is_unchanged())
+                    .map(|(_, &index)| index)),
+                DuplicateResolution::Abort => return Err(duplicate.target)
+            }
+        }
+
+        skipped.sort();
+        let mut removed: Vec<Rename> = skipped.into_iter().rev().map(|index| self.renames.remove(index)).collect();
+        removed.reverse();
+        self.skipped.extend(removed);
+        Ok(())
+    }
+
+    /// Checks the plan as a whole, and returns every conflict that was found.
+    pub fn validate(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for (index, rename) in self.renames.iter().enumerate().filter(|&(_, rename)| !rename.is_unchanged()) {
+            if self.renames[..index].iter().any(|other| other.target == rename.target) {
+                conflicts.push(Conflict::DuplicateTarget(rename.target.clone()));
+            } else if self.target_exists(rename) {
+                conflicts.push(Conflict::TargetExists(rename.target.clone()));
+            }
+        }
+        conflicts
+    }
+
+    /// Performs each rename within the plan, in order, with the plan's transfer mode, and sets the modification
+    /// time of each target with an air date. The progress of copies is reported with the source of the episode
+    /// being copied. If any rename fails, every rename which was already performed is reverted in reverse order,
+    /// so that the season is not left partially renamed, and the original modification times are restored.
+    ///
+    /// When the target is the original file, as with renames and hard links, the previous modification time of
+    /// each target whose time was set is returned, so that it may be restored when the run is undone.
+    pub fn apply<F: FnMut(&Path, u64, u64)>(&self, progress: F) -> Result<Vec<(PathBuf, i64)>, ApplyError> {
+        let changed: Vec<&Rename> = self.renames.iter().filter(|rename| !rename.is_unchanged()).collect();
+        let renames: Vec<(PathBuf, PathBuf)> = changed.iter()
+            .map(|rename| (rename.source.clone(), rename.target.clone()))
+            .collect();
+        let shares_file = self.transfer.shares_file();
+        let mut previous: Vec<(usize, i64)> = Vec::new();
+        let overwrite: Vec<bool> = changed.iter().map(|rename| rename.overwrite).collect();
+        let result = apply_renames_with(&renames, &overwrite, self.transfer, progress, |index| {
+            match changed[index].air_date {
+                Some(date) => {
+                    let target = &changed[index].target;
+                    if shares_file { previous.push((index, transfer::modified(target)?)); }
+                    transfer::set_modified(target, date.timestamp())
+                },
+                None => Ok(())
+            }
+        });
+
+        match result {
+            Ok(()) => Ok(previous.into_iter().map(|(index, time)| (changed[index].target.clone(), time)).collect()),
+            Err(why) => {
+                // Renames which could not be reverted leave the file at its target, where its time is restored.
+                for (index, time) in previous {
+                    let rename = changed[index];
+                    let reverted = !why.rollback_failures.iter().any(|(_, to, _)| *to == rename.target);
+                    let _ = transfer::set_modified(if reverted { &rename.source } else { &rename.target }, time);
+                }
+                Err(why)
+            }
+        }
+    }
+}
+
+/// The temporary name that a file is moved to while its name is needed by the target of another rename. The file
+/// is kept within the same directory, so that it remains on the same filesystem.
+fn temporary_name(source: &Path, index: usize) -> PathBuf {
+    source.with_file_name(format!(".tv-renamer-{}-{}.tmp", process::id(), index))
+}
+
+/// The temporary name that a file which is to be overwritten by the target of a rename is moved to, until the
+/// batch of renames has succeeded.
+fn replaced_name(target: &Path, index: usize) -> PathBuf {
+    target.with_file_name(format!(".tv-renamer-{}-{}.replaced.tmp", process::id(), index))
+}
+
+/// Performs each rename, as a source and target, in order, with the given transfer mode. When the target of a
+/// rename is the source of another, as in a chain or a swap, the files whose names are needed are first moved
+/// aside to temporary names, so that no file is overwritten by another within the same batch. If any rename fails,
+/// every rename which was already performed is reverted in reverse order.
+pub fn apply_renames<F>(renames: &[(PathBuf, PathBuf)], mode: TransferMode, progress: F) -> Result<(), ApplyError>
+    where F: FnMut(&Path, u64, u64)
+{
+    apply_renames_with(renames, &vec![false; renames.len()], mode, progress, |_| Ok(()))
+}
+
+/// Performs each rename as with `apply_renames`, and then calls `finish` with the index of each rename once its
+/// target is in place. An error from `finish` fails the rename, and reverts it along with every earlier rename.
+///
+/// A file which already exists at the target of a rename that may overwrite it is moved aside to a temporary name
+/// rather than being replaced, so that it is restored if the batch is reverted. The files which were moved aside
+/// are only deleted once every rename has succeeded.
+fn apply_renames_with<F, G>(renames: &[(PathBuf, PathBuf)], overwrite: &[bool], mode: TransferMode, mut progress: F,
+    mut finish: G) -> Result<(), ApplyError>
+    where F: FnMut(&Path, u64, u64),
+          G: FnMut(usize) -> io::Result<()>
+{
+    // Each step is a transfer on the filesystem, along with the index of the rename that it belongs to, and the
+    // mode of the transfer. Moves to temporary names are always renames within the same directory.
+    let mut steps: Vec<(PathBuf, PathBuf, usize, TransferMode)> = Vec::with_capacity(renames.len());
+    let mut sources: Vec<PathBuf> = renames.iter().map(|(source, _)| source.clone()).collect();
+    for (index, (source, _)) in renames.iter().enumerate() {
+        if mode.removes_source()
+            && renames.iter().enumerate().any(|(other, (_, target))| other != index && target == source)
+        {
+            let temporary = temporary_name(source, index);
+            steps.push((source.clone(), temporary.clone(), index, TransferMode::Rename));
+            sources[index] = temporary;
+        }
+    }
+    // A target which is the source of another rename has already been moved aside above.
+    let mut replaced: Vec<PathBuf> = Vec::new();
+    for (index, (_, target)) in renames.iter().enumerate() {
+        if overwrite[index] && fs::symlink_metadata(target).is_ok()
+            && !(mode.removes_source() && renames.iter().any(|(source, _)| source == target))
+        {
+            let temporary = replaced_name(target, index);
+            steps.push((target.clone(), temporary.clone(), index, TransferMode::Rename));
+            replaced.push(temporary);
+        }
+    }
+    for (index, (_, target)) in renames.iter().enumerate() {
+        steps.push((sources[index].clone(), target.clone(), index, mode));
+    }
+
+    let mut performed: Vec<(PathBuf, PathBuf, TransferMode)> = Vec::with_capacity(steps.len());
+    for (from, to, index, mode) in steps {
+        let source = &renames[index].0;
+        let finished = to == renames[index].1;
+        // A target which is in place but cannot be finished is reverted along with the earlier renames.
+        let result = transfer::transfer(&from, &to, mode, |copied, total| progress(source, copied, total))
+            .map(|_| performed.push((from, to, mode)))
+            .and_then(|_| if finished { finish(index) } else { Ok(()) });
+        if let Err(why) = result {
+            let mut rollback_failures = Vec::new();
+            for (from, to, mode) in performed.iter().rev().cloned() {
+                if let Err(why) = transfer::revert(&from, &to, mode) {
+                    rollback_failures.push((from, to, why));
+                }
+            }
+
+            return Err(ApplyError {
+                source:            renames[index].0.clone(),
+                target:            renames[index].1.clone(),
+                why:               why,
+                reverted:          performed.len() - rollback_failures.len(),
+                rollback_failures: rollback_failures
+            });
+        }
+    }
+
+    // The batch can no longer be reverted, so the files which were replaced are no longer needed. A file which
+    // cannot be deleted is left behind under its temporary name, rather than failing renames which succeeded.
+    for path in replaced {
+        let _ = fs::remove_file(path);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_validate() {
+    let plan = RenamePlan { renames: vec![
+        Rename::new("/nonexistent/one.mkv", "/nonexistent/Series - 1x01.mkv"),
+        Rename::new("/nonexistent/two.mkv", "/nonexistent/Series - 1x01.mkv"),
+        Rename::new("/nonexistent/Series - 1x02.mkv", "/nonexistent/Series - 1x02.mkv"),
+    ], skipped: Vec::new(), transfer: TransferMode::Rename };
+    assert_eq!(plan.validate(), vec![Conflict::DuplicateTarget(PathBuf::from("/nonexistent/Series - 1x01.mkv"))]);
+}
+
+#[test]
+fn test_apply_rollback() {
+    let directory = TempDir::new("rollback");
+    fs::File::create(directory.join("one.mkv")).unwrap();
+
+    let rename = |source: &str, target: &str| Rename::new(directory.join(source), directory.join(target));
+    let plan = RenamePlan { renames: vec![
+        rename("one.mkv", "1x01.mkv"),
+        rename("missing.mkv", "1x02.mkv"),
+    ], skipped: Vec::new(), transfer: TransferMode::Rename };
+
+    let why = plan.apply(|_, _, _| ()).unwrap_err();
+    assert_eq!(why.source, directory.join("missing.mkv"));
+    assert!(why.rolled_back() && why.reverted == 1);
+    assert!(directory.join("one.mkv").exists() && !directory.join("1x01.mkv").exists());
+}
+
+#[test]
+fn test_apply_restores_modified() {
+    let directory = TempDir::new("restore");
+    fs::File::create(directory.join("one.mkv")).unwrap();
+    transfer::set_modified(&directory.join("one.mkv"), 1000000000).unwrap();
+
+    let rename = |source: &str, target: &str| Rename {
+        air_date: Some(AirDate { year: 2010, month: 1, day: 1 }),
+        ..Rename::new(directory.join(source), directory.join(target))
+    };
+    let plan = |transfer: TransferMode| RenamePlan { renames: vec![
+        rename("one.mkv", "1x01.mkv"),
+        rename("missing.mkv", "1x02.mkv"),
+    ], skipped: Vec::new(), transfer: transfer };
+
+    // The original's time is changed through the hard link, and is restored when the season is reverted.
+    for &mode in &[TransferMode::Rename, TransferMode::Hardlink] {
+        assert!(plan(mode).apply(|_, _, _| ()).is_err());
+        assert_eq!(transfer::modified(&directory.join("one.mkv")).unwrap(), 1000000000);
+        assert!(!directory.join("1x01.mkv").exists());
+    }
+
+    let mut applied = plan(TransferMode::Hardlink);
+    applied.renames.pop();
+    assert_eq!(applied.apply(|_, _, _| ()).unwrap(), vec![(directory.join("1x01.mkv"), 1000000000)]);
+    assert_eq!(transfer::modified(&directory.join("one.mkv")).unwrap(), AirDate { year: 2010, month: 1, day: 1 }
+        .timestamp());
+}
+
+#[test]
+fn test_apply_overwrite() {
+    use std::io::Read;
+
+    let directory = TempDir::new("overwrite");
+    fs::File::create(directory.join("one.mkv")).unwrap();
+    fs::write(directory.join("1x01.mkv"), b"existing").unwrap();
+    let read = |name: &str| {
+        let mut contents = String::new();
+        fs::File::open(directory.join(name)).unwrap().read_to_string(&mut contents).unwrap();
+        contents
+    };
+
+    // The file which was to be replaced is restored when the season is reverted.
+    let rename = |source: &str, target: &str| Rename {
+        overwrite: true,
+        ..Rename::new(directory.join(source), directory.join(target))
+    };
+    let plan = |transfer: TransferMode, renames: Vec<Rename>| {
+        RenamePlan { renames: renames, skipped: Vec::new(), transfer: transfer }
+    };
+    for &mode in &[TransferMode::Rename, TransferMode::Copy, TransferMode::Hardlink] {
+        let why = plan(mode, vec![rename("one.mkv", "1x01.mkv"), rename("missing.mkv", "1x02.mkv")])
+            .apply(|_, _, _| ()).unwrap_err();
+        assert!(why.rolled_back());
+        assert_eq!(read("1x01.mkv"), "existing");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+    }
+
+    // Once every rename has succeeded, the file which was replaced is deleted.
+    plan(TransferMode::Rename, vec![rename("one.mkv", "1x01.mkv")]).apply(|_, _, _| ()).unwrap();
+    assert_eq!(read("1x01.mkv"), "");
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+}
+
+#[test]
+fn test_apply_chains() {
+    use std::io::{Read, Write};
+
+    let directory = TempDir::new("chains");
+    for name in &["1", "2", "3", "a", "b"] {
+        fs::File::create(directory.join(name)).unwrap().write_all(name.as_bytes()).unwrap();
+    }
+    let read = |name: &str| {
+        let mut contents = String::new();
+        fs::File::open(directory.join(name)).unwrap().read_to_string(&mut contents).unwrap();
+        contents
+    };
+
+    // A shift of every episode by one, followed by a swap of two episodes.
+    let pairs = [("3", "4"), ("2", "3"), ("1", "2"), ("a", "b"), ("b", "a")];
+    let renames: Vec<(PathBuf, PathBuf)> = pairs.iter()
+        .map(|&(source, target)| (directory.join(source), directory.join(target)))
+        .collect();
+    apply_renames(&renames, TransferMode::Rename, |_, _, _| ()).unwrap();
+
+    assert!(!directory.join("1").exists());
+    assert_eq!((read("2"), read("3"), read("4")), ("1".to_owned(), "2".to_owned(), "3".to_owned()));
+    assert_eq!((read("a"), read("b")), ("b".to_owned(), "a".to_owned()));
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 5);
+}
+
+#[test]
+fn test_resolve_conflicts() {
+    let directory = TempDir::new("conflicts");
+    for name in &["one.mkv", "two.mkv", "1x01.mkv", "1x02.mkv", "1x02 (2).mkv"] {
+        fs::File::create(directory.join(name)).unwrap();
+    }
+    let plan = |policy: ConflictPolicy| {
+        let rename = |source: &str, target: &str| Rename::new(directory.join(source), directory.join(target));
+        let mut plan = RenamePlan { renames: vec![
+            rename("one.mkv", "1x01.mkv"),
+            rename("two.mkv", "1x02.mkv"),
+        ], skipped: Vec::new(), transfer: TransferMode::Rename };
+        let resolved = plan.resolve_conflicts(policy, |rename| {
+            if rename.source.ends_with("one.mkv") { Resolution::Skip } else { Resolution::Overwrite }
+        });
+        (plan, resolved)
+    };
+
+    let (skipped, resolved) = plan(ConflictPolicy::Skip);
+    assert!(resolved.is_ok() && skipped.renames.is_empty() && skipped.skipped.len() == 2);
+
+    let (suffixed, _) = plan(ConflictPolicy::Suffix);
+    let targets: Vec<&Path> = suffixed.renames.iter().map(|rename| rename.target.as_path()).collect();
+    assert_eq!(targets, vec![directory.join("1x01 (2).mkv").as_path(), directory.join("1x02 (3).mkv").as_path()]);
+    assert!(suffixed.validate().is_empty());
+
+    let (asked, _) = plan(ConflictPolicy::Ask);
+    assert!(asked.skipped.len() == 1 && asked.renames[0].overwrite && asked.validate().is_empty());
+
+    let (_, aborted) = plan(ConflictPolicy::Abort);
+    assert_eq!(aborted, Err(directory.join("1x01.mkv")));
+}
+
+#[test]
+fn test_resolve_duplicates() {
+    let plan = |policy: ConflictPolicy, resolution: DuplicateResolution| {
+        let mut plan = RenamePlan { renames: vec![
+            Rename::new("/nonexistent/S01E03.720p.HDTV.mkv", "/nonexistent/1x03.mkv"),
+            Rename::new("/nonexistent/S01E03.1080p.BluRay.mkv", "/nonexistent/1x03.mkv"),
+            Rename::new("/nonexistent/S01E04.mkv", "/nonexistent/1x04.mkv"),
+        ], skipped: Vec::new(), transfer: TransferMode::Rename };
+        let resolved = plan.resolve_duplicates(policy, |duplicate| {
+            assert_eq!(duplicate.candidates[1].describe(), "S01E03.1080p.BluRay.mkv (1080p BluRay)");
+            resolution
+        });
+        (plan, resolved)
+    };
+    let targets = |plan: &RenamePlan| plan.renames.iter()
+        .map(|rename| rename.target.to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+
+    let (suffixed, _) = plan(ConflictPolicy::Suffix, DuplicateResolution::Abort);
+    assert_eq!(targets(&suffixed), vec!["/nonexistent/1x03.mkv", "/nonexistent/1x03 (2).mkv", "/nonexistent/1x04.mkv"]);
+
+    let (kept, _) = plan(ConflictPolicy::Ask, DuplicateResolution::Keep(1));
+    assert_eq!(kept.skipped[0].source, PathBuf::from("/nonexistent/S01E03.720p.HDTV.mkv"));
+    assert_eq!(targets(&kept), vec!["/nonexistent/1x03.mkv", "/nonexistent/1x04.mkv"]);
+
+    let (_, aborted) = plan(ConflictPolicy::Overwrite, DuplicateResolution::Suffix);
+    assert_eq!(aborted, Err(PathBuf::from("/nonexistent/1x03.mkv")));
+    assert_eq!(format_size(1536 * 1024 * 1024), "1.5 GiB");
+}
@@ -0,0 +1,232 @@
+#[cfg(unix)]
+use std::ffi::CString;
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(unix)]
+use libc;
+
+use super::plan::{Conflict, Rename, RenamePlan};
+use super::transfer::TransferMode;
+use super::sanitize::NAME_MAX;
+
+quick_error! {
+    /// A problem which would cause a rename to fail, and which is found before any file is touched.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Problem {
+        SourceMissing(source: PathBuf) {
+            display("{:?} no longer exists", source)
+        }
+        ReadOnly(directory: PathBuf) {
+            display("{:?} is not writable", directory)
+        }
+        NameTooLong(target: PathBuf) {
+            display("{:?} contains a name which is longer than {} bytes", target, NAME_MAX)
+        }
+        TargetExists(target: PathBuf) {
+            display("{:?} already exists", target)
+        }
+        DuplicateTarget(target: PathBuf) {
+            display("more than one episode would be renamed to {:?}", target)
+        }
+        DuplicateDirectory(target: PathBuf) {
+            display("more than one directory would be renamed to {:?}", target)
+        }
+    }
+}
+
+impl Problem {
+    /// Whether the problem would cause the given rename to fail.
+    pub fn affects(&self, rename: &Rename, transfer: TransferMode) -> bool {
+        match *self {
+            Problem::SourceMissing(ref source) => &rename.source == source,
+            Problem::ReadOnly(ref directory) => parent_directory(&rename.target) == directory.as_path()
+                || (transfer.removes_source() && parent_directory(&rename.source) == directory.as_path()),
+            Problem::NameTooLong(ref target) | Problem::TargetExists(ref target)
+                | Problem::DuplicateTarget(ref target) => &rename.target == target,
+            Problem::DuplicateDirectory(_) => false
+        }
+    }
+
+    /// Whether the problem would cause the rename of a directory to the given target to fail.
+    pub fn affects_directory(&self, target: &Path) -> bool {
+        match *self {
+            Problem::TargetExists(ref path) | Problem::DuplicateDirectory(ref path) => path == target,
+            _ => false
+        }
+    }
+}
+
+/// The directory that will contain a path, where an empty parent refers to the current directory.
+fn parent_directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new(".")
+    }
+}
+
+/// Whether new entries may be created within the directory. Directories which do not exist yet will be created
+/// within their nearest ancestor which does exist, so that ancestor is checked instead.
+fn writable(directory: &Path) -> bool {
+    let existing = directory.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(directory);
+    may_write(existing)
+}
+
+/// Asks the system whether the user may write to the directory, which accounts for its owner and group.
+#[cfg(unix)]
+fn may_write(directory: &Path) -> bool {
+    CString::new(directory.as_os_str().as_bytes())
+        .map(|path| unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0)
+        .unwrap_or(false)
+}
+
+/// Other platforms only check whether the directory is marked as read-only.
+#[cfg(not(unix))]
+fn may_write(directory: &Path) -> bool {
+    fs::metadata(directory).map(|metadata| !metadata.permissions().readonly()).unwrap_or(false)
+}
+
+/// Checks every rename within the plans of a series before any of them are applied, and returns every problem that
+/// was found, so that they may all be reported at once.
+pub fn check(plans: &[RenamePlan]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut directories: Vec<&Path> = Vec::new();
+    let mut targets: Vec<&Path> = Vec::new();
+
+    for plan in plans {
+        for conflict in plan.validate() {
+            problems.push(match conflict {
+                Conflict::TargetExists(target) => Problem::TargetExists(target),
+                Conflict::DuplicateTarget(target) => Problem::DuplicateTarget(target)
+            });
+        }
+
+        for rename in plan.renames.iter().filter(|rename| !rename.is_unchanged()) {
+            if fs::symlink_metadata(&rename.source).is_err() {
+                problems.push(Problem::SourceMissing(rename.source.clone()));
+            }
+
+            let too_long = rename.target.components().any(|component| match component {
+                Component::Normal(name) => name.len() > NAME_MAX,
+                _ => false
+            });
+            if too_long { problems.push(Problem::NameTooLong(rename.target.clone())); }
+
+            // Duplicates within a plan are found by validating the plan, so only earlier seasons are compared here.
+            if targets.contains(&rename.target.as_path()) {
+                problems.push(Problem::DuplicateTarget(rename.target.clone()));
+            }
+
+            // Transfers which keep their sources do not modify the directory of the source.
+            let mut modified = vec![parent_directory(&rename.target)];
+            if plan.transfer.removes_source() { modified.push(parent_directory(&rename.source)); }
+            for directory in modified {
+                if directories.contains(&directory) { continue }
+                directories.push(directory);
+                if !writable(directory) { problems.push(Problem::ReadOnly(directory.to_path_buf())); }
+            }
+        }
+
+        targets.extend(plan.renames.iter()
+            .filter(|rename| !rename.is_unchanged())
+            .map(|rename| rename.target.as_path()));
+    }
+    problems
+}
+
+/// Checks the renames of the season and series directories, which are applied after the episodes, for targets which
+/// already exist or which more than one directory would be renamed to.
+pub fn check_directories(renames: &[(PathBuf, PathBuf)]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut targets: Vec<&Path> = Vec::new();
+    for (source, target) in renames.iter().filter(|(source, target)| source != target) {
+        if targets.contains(&target.as_path()) {
+            problems.push(Problem::DuplicateDirectory(target.clone()));
+        } else if fs::symlink_metadata(target).is_ok() {
+            problems.push(Problem::TargetExists(target.clone()));
+        } else if source.exists() && !writable(parent_directory(source)) {
+            problems.push(Problem::ReadOnly(parent_directory(source).to_path_buf()));
+        }
+        targets.push(target);
+    }
+    problems
+}
+
+/// Removes every rename which is affected by a problem from the plans, so that the remaining renames may be applied,
+/// and returns each removed rename along with the problem that affected it. Plans are checked again after each
+/// removal, as removing a rename may leave another without its original target directory.
+pub fn exclude(plans: &mut [RenamePlan]) -> Vec<(Rename, Problem)> {
+    let mut excluded = Vec::new();
+    loop {
+        let problems = check(plans);
+        let before = excluded.len();
+        for plan in plans.iter_mut() {
+            let transfer = plan.transfer;
+            let mut index = 0;
+            while index < plan.renames.len() {
+                let problem = if plan.renames[index].is_unchanged() { None } else {
+                    problems.iter().find(|problem| problem.affects(&plan.renames[index], transfer)).cloned()
+                };
+                match problem {
+                    Some(problem) => excluded.push((plan.renames.remove(index), problem)),
+                    None => index += 1
+                }
+            }
+        }
+        // A problem which affects none of the renames cannot be resolved by removing renames.
+        if problems.is_empty() || excluded.len() == before { return excluded }
+    }
+}
+
+#[test]
+fn test_check() {
+    use super::plan::Rename;
+    use super::transfer::TransferMode;
+
+    let plan = |renames| RenamePlan { renames: renames, skipped: Vec::new(), transfer: TransferMode::Rename };
+    let long = format!("/nonexistent/{}.mkv", "a".repeat(NAME_MAX));
+    let plans = [
+        plan(vec![
+            Rename::new("/nonexistent/one.mkv", "/nonexistent/1x01.mkv"),
+            Rename::new("/nonexistent/two.mkv", long.clone())
+        ]),
+        plan(vec![Rename::new("/nonexistent/three.mkv", "/nonexistent/1x01.mkv")]),
+    ];
+
+    let problems = check(&plans);
+    assert!(problems.contains(&Problem::SourceMissing(PathBuf::from("/nonexistent/one.mkv"))));
+    assert!(problems.contains(&Problem::NameTooLong(PathBuf::from(long))));
+    assert!(problems.contains(&Problem::DuplicateTarget(PathBuf::from("/nonexistent/1x01.mkv"))));
+    // Whether `/` is writable depends upon the user running the tests.
+    assert_eq!(problems.iter().filter(|problem| !matches!(**problem, Problem::ReadOnly(_))).count(), 5);
+
+    let mut plans = plans;
+    let excluded = exclude(&mut plans);
+    assert_eq!(excluded.len(), 3);
+    assert!(plans.iter().all(|plan| plan.renames.is_empty()));
+    assert_eq!(excluded[1].0.source, PathBuf::from("/nonexistent/two.mkv"));
+}
+
+#[test]
+fn test_check_directories() {
+    use super::TempDir;
+
+    let root = TempDir::new("directories");
+    fs::create_dir_all(root.join("Season 1")).unwrap();
+    fs::create_dir_all(root.join("Season 2")).unwrap();
+    fs::create_dir_all(root.join("Specials")).unwrap();
+
+    let renames = [
+        (root.join("Season 1"), root.join("Season 01")),
+        (root.join("Season 2"), root.join("Season 01")),
+        (root.join("Season 0"), root.join("Specials"))
+    ];
+    let problems = check_directories(&renames);
+    assert_eq!(problems, vec![Problem::DuplicateDirectory(root.join("Season 01")),
+        Problem::TargetExists(root.join("Specials"))]);
+    assert!(problems[0].affects_directory(&root.join("Season 01")));
+    assert!(check_directories(&renames[..1]).is_empty());
+    assert!(writable(&root.join("Season 1/Extras")));
+}
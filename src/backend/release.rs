@@ -0,0 +1,164 @@
+/// Information about a release that is commonly embedded within the file names of scene and fansub releases,
+/// such as `Series.S01E01.1080p.BluRay.x265-GROUP`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Release {
+    pub resolution:    Option<String>,
+    pub source:        Option<String>,
+    pub codec:         Option<String>,
+    pub group:         Option<String>,
+    pub proper:        bool,
+    pub original_name: String
+}
+
+impl Release {
+    /// Describes the quality of the release, such as `1080p BluRay x265 PROPER`, if any of it is known.
+    pub fn quality(&self) -> Option<String> {
+        let fields: Vec<&str> = [&self.resolution, &self.source, &self.codec].iter()
+            .filter_map(|field| field.as_ref().map(String::as_str))
+            .chain(if self.proper { Some("PROPER") } else { None })
+            .collect();
+        if fields.is_empty() { None } else { Some(fields.join(" ")) }
+    }
+
+    /// The source and resolution of the release in the style of Sonarr's quality title, such as `BluRay-1080p`.
+    /// The hyphen is only included when both are known.
+    pub fn quality_title(&self) -> Option<String> {
+        let fields: Vec<&str> = [&self.source, &self.resolution].iter()
+            .filter_map(|field| field.as_ref().map(String::as_str))
+            .collect();
+        if fields.is_empty() { None } else { Some(fields.join("-")) }
+    }
+
+    /// The quality title followed by `PROPER` if the release is a proper, as in Sonarr's full quality.
+    pub fn quality_full(&self) -> Option<String> {
+        let fields: Vec<String> = self.quality_title().into_iter()
+            .chain(if self.proper { Some("PROPER".to_owned()) } else { None })
+            .collect();
+        if fields.is_empty() { None } else { Some(fields.join(" ")) }
+    }
+}
+
+/// Parses the stem of a release's file name to collect the resolution, source, codec, and group of the release.
+pub fn parse(stem: &str) -> Release {
+    let mut release = Release { original_name: stem.to_owned(), ..Release::default() };
+
+    // Fansub releases place the name of the group in brackets at the start of the file name, whereas scene releases
+    // append the group to the end of the name, following a dash.
+    if let Some(rest) = stem.strip_prefix('[') {
+        release.group = rest.split(']').next().filter(|group| !group.is_empty()).map(String::from);
+    } else if let Some(word) = stem.rsplit(['.', ' ', '_']).next() {
+        // The whole word is checked first, so that the dash within a source such as `WEB-DL` is not mistaken for
+        // the dash before a group.
+        let group = word.rsplit('-').next().filter(|_| word.contains('-') && !is_release_info(word));
+        if let Some(group) = group {
+            if !group.is_empty() && !group.contains(']') && !is_release_info(group) && !is_episode_marker(group) {
+                release.group = Some(group.to_owned());
+            }
+        }
+    }
+
+    let stem = stem.replace("H.264", "H264").replace("h.264", "h264").replace("H.265", "H265").replace("h.265", "h265");
+    for word in stem.split(['.', ' ', '_', '[', ']', '(', ')']) {
+        // Words such as `x265-GROUP` and `WEB-DL` are matched both whole and in parts.
+        for word in Some(word).into_iter().chain(word.split('-')) {
+            if release.resolution.is_none() { release.resolution = parse_resolution(word); }
+            if release.source.is_none() { release.source = parse_source(word).map(String::from); }
+            if release.codec.is_none() { release.codec = parse_codec(word).map(String::from); }
+            match word.to_lowercase().as_str() {
+                "proper" | "repack" => release.proper = true,
+                _ => ()
+            }
+        }
+    }
+
+    release
+}
+
+/// Whether the word describes the quality of the release, rather than naming the group.
+fn is_release_info(word: &str) -> bool {
+    parse_resolution(word).is_some() || parse_source(word).is_some() || parse_codec(word).is_some()
+        || word.eq_ignore_ascii_case("proper") || word.eq_ignore_ascii_case("repack")
+}
+
+/// Whether the word numbers an episode, as in `S01E01`, `S01E01E02`, or `1x01`.
+fn is_episode_marker(word: &str) -> bool {
+    let word = word.to_lowercase();
+    let is_number = |value: &str| !value.is_empty() && value.chars().all(|c| c.is_ascii_digit());
+    let scene = word.starts_with('s') && word[1..].split('e').count() >= 2 && word[1..].split('e').all(is_number);
+    let numbered = word.split('x').count() == 2 && word.split('x').all(is_number);
+    scene || numbered
+}
+
+fn parse_resolution(word: &str) -> Option<String> {
+    let word = word.to_lowercase();
+    match word.as_str() {
+        "4k" | "uhd" => return Some("2160p".to_owned()),
+        _ => ()
+    }
+
+    if !word.ends_with('p') && !word.ends_with('i') { return None }
+    match &word[..word.len() - 1] {
+        "480" | "576" | "720" | "1080" | "2160" => Some(word.clone()),
+        _ => None
+    }
+}
+
+fn parse_source(word: &str) -> Option<&'static str> {
+    match word.to_lowercase().as_str() {
+        "bluray" | "blu-ray" | "bdrip" | "brrip" | "bd" => Some("BluRay"),
+        "remux"                                         => Some("Remux"),
+        "web-dl" | "webdl"                              => Some("WEB-DL"),
+        "webrip" | "web-rip"                            => Some("WEBRip"),
+        "web"                                           => Some("WEB"),
+        "hdtv"                                          => Some("HDTV"),
+        "pdtv" | "sdtv"                                 => Some("SDTV"),
+        "dvdrip" | "dvd"                                => Some("DVD"),
+        _                                               => None
+    }
+}
+
+fn parse_codec(word: &str) -> Option<&'static str> {
+    match word.to_lowercase().as_str() {
+        "x264"          => Some("x264"),
+        "x265"          => Some("x265"),
+        "h264" | "avc"  => Some("H.264"),
+        "h265" | "hevc" => Some("HEVC"),
+        "xvid"          => Some("XviD"),
+        "divx"          => Some("DivX"),
+        "av1"           => Some("AV1"),
+        "vp9"           => Some("VP9"),
+        _               => None
+    }
+}
+
+#[test]
+fn test_parse_scene() {
+    assert_eq!(parse("Series.S01E01.PROPER.1080p.BluRay.x265-GROUP"), Release {
+        resolution:    Some("1080p".to_owned()),
+        source:        Some("BluRay".to_owned()),
+        codec:         Some("x265".to_owned()),
+        group:         Some("GROUP".to_owned()),
+        proper:        true,
+        original_name: "Series.S01E01.PROPER.1080p.BluRay.x265-GROUP".to_owned()
+    });
+    assert_eq!(parse("Series S01E01 720p WEB-DL H.264").source, Some("WEB-DL".to_owned()));
+    assert_eq!(parse("Series S01E01 720p WEB-DL H.264").group, None);
+    assert_eq!(parse("Series.S01E01.720p.WEB-DL").group, None);
+    assert_eq!(parse("Show-S01E01").group, None);
+    assert_eq!(parse("Show-1x01").group, None);
+    assert_eq!(parse("Series.S01E01.720p.WEB-DL.x264-GROUP").group, Some("GROUP".to_owned()));
+    assert_eq!(parse("Series.S01E01.PROPER.1080p.BluRay.x265-GROUP").quality(), Some("1080p BluRay x265 PROPER".to_owned()));
+    assert_eq!(parse("Series - 01").quality(), None);
+    assert_eq!(parse("Series.S01E01.PROPER.1080p.BluRay.x265-GROUP").quality_full(), Some("BluRay-1080p PROPER".to_owned()));
+    assert_eq!(parse("Series.S01E01.1080p.x265-GROUP").quality_title(), Some("1080p".to_owned()));
+    assert_eq!(parse("Series.S01E01.PROPER.x265-GROUP").quality_full(), Some("PROPER".to_owned()));
+}
+
+#[test]
+fn test_parse_fansub() {
+    let release = parse("[Group] Series - 01 [1080p][HEVC]");
+    assert_eq!(release.group, Some("Group".to_owned()));
+    assert_eq!(release.resolution, Some("1080p".to_owned()));
+    assert_eq!(release.codec, Some("HEVC".to_owned()));
+    assert!(!release.proper);
+}
@@ -0,0 +1,142 @@
+use super::media::MediaInfo;
+use super::metadata::{Episode, Series};
+use super::release::Release;
+use super::tokenizer::TemplateToken as Token;
+use super::traits::Digits;
+
+/// All of the values which may be substituted into a template.
+pub struct TemplateValues<'a> {
+    pub season_no:   u8,
+    pub episode_no:  u16,
+    pub pad_length:  u8,
+    pub series_name: &'a str,
+    pub series:      &'a Series,
+    pub episode:     &'a Episode,
+    pub release:     &'a Release,
+    pub media:       &'a MediaInfo,
+    pub crc32:       Option<u32>,
+    pub part:        Option<u8>
+}
+
+/// Substitutes the values into each token of the template. Any `/` characters from the template are preserved,
+/// whereas values may not introduce path separators of their own.
+pub fn render(template: &[Token], values: &TemplateValues) -> String {
+    macro_rules! push_value {
+        ($filename:ident, $value:expr) => { $filename.push_str(&$value.replace("/", "-")) }
+    }
+
+    let mut filename = String::with_capacity(64);
+    for pattern in template {
+        match *pattern {
+            Token::Character(value) => filename.push(value),
+            Token::Series           => push_value!(filename, values.series_name),
+            Token::Season           => filename.push_str(&values.season_no.to_string()),
+            Token::Episode          => filename.push_str(&values.episode_no.to_padded_string('0', values.pad_length as usize)),
+            Token::TvdbTitle        => push_value!(filename, values.episode.title),
+            Token::TvdbFirstAired   => if let Some(date) = values.episode.first_aired {
+                filename.push_str(&date.year.to_string());
+                filename.push('-');
+                filename.push_str(&date.month.to_padded_string('0', 2));
+                filename.push('-');
+                filename.push_str(&date.day.to_padded_string('0', 2));
+            },
+            Token::Resolution       => if let Some(ref value) = values.release.resolution { filename.push_str(value) },
+            Token::Source           => if let Some(ref value) = values.release.source { filename.push_str(value) },
+            Token::Codec            => if let Some(ref value) = values.release.codec { filename.push_str(value) },
+            Token::Group            => if let Some(ref value) = values.release.group { push_value!(filename, value) },
+            Token::Proper           => if values.release.proper { filename.push_str("PROPER") },
+            Token::Quality          => if let Some(value) = values.release.quality_title() {
+                filename.push_str(&value)
+            },
+            Token::QualityFull      => if let Some(value) = values.release.quality_full() {
+                filename.push_str(&value)
+            },
+            Token::OriginalName     => push_value!(filename, values.release.original_name),
+            Token::VideoHeight      => if let Some(value) = values.media.video_height {
+                filename.push_str(&value.to_string())
+            },
+            Token::VideoCodec       => if let Some(ref value) = values.media.video_codec { push_value!(filename, value) },
+            Token::AudioCodec       => if let Some(ref value) = values.media.audio_codec { push_value!(filename, value) },
+            Token::AudioChannels    => if let Some(value) = values.media.channel_layout() { filename.push_str(&value) },
+            Token::Hdr              => if let Some(value) = values.media.hdr { filename.push_str(value) },
+            Token::Duration         => if let Some(value) = values.media.duration {
+                filename.push_str(&(value / 60).to_string())
+            },
+            Token::SeriesYear       => if let Some(value) = values.series.year { filename.push_str(&value.to_string()) },
+            Token::Network          => if let Some(ref value) = values.series.network { push_value!(filename, value) },
+            Token::TvdbSeriesId     => filename.push_str(&values.series.id.to_string()),
+            Token::ImdbId           => if let Some(ref value) = values.series.imdb_id { push_value!(filename, value) },
+            Token::SeriesOfficialName => push_value!(filename, values.series.name),
+            Token::TvdbEpisodeId    => filename.push_str(&values.episode.id.to_string()),
+            Token::ProductionCode   => if let Some(ref value) = values.episode.production_code {
+                push_value!(filename, value)
+            },
+            Token::Rating           => if let Some(ref value) = values.episode.rating { filename.push_str(value) },
+            Token::Director         => if let Some(ref value) = values.episode.director { push_value!(filename, value) },
+            Token::Writer           => if let Some(ref value) = values.episode.writer { push_value!(filename, value) },
+            Token::Runtime          => if let Some(value) = values.media.duration {
+                filename.push_str(&(value / 60).to_string())
+            },
+            Token::Crc32            => if let Some(value) = values.crc32 { filename.push_str(&format!("{:08X}", value)) },
+            Token::Part             => if let Some(value) = values.part { filename.push_str(&format!("pt{}", value)) },
+            Token::SeasonPadded(pad)  => {
+                filename.push_str(&(values.season_no as u16).to_padded_string('0', pad as usize))
+            },
+            Token::EpisodePadded(pad) => filename.push_str(&values.episode_no.to_padded_string('0', pad as usize)),
+            Token::Dotted(ref token) => filename.push_str(&dotted(&render(&[(**token).clone()], values)))
+        }
+    }
+
+    filename
+}
+
+/// Separates the words of a value with dots in the style of scene releases, as in `Marvels.Agents.of.SHIELD`.
+/// Punctuation other than hyphens and dots is removed.
+fn dotted(value: &str) -> String {
+    value.split_whitespace()
+        .map(|word| word.chars().filter(|&c| c.is_alphanumeric() || c == '-' || c == '.').collect::<String>())
+        .map(|word| word.trim_matches('.').to_owned())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+#[cfg(test)]
+fn render_preset(name: &str) -> String {
+    use super::tokenizer::{self, PRESETS};
+
+    let template = PRESETS.iter().find(|&&(preset, _)| preset == name).unwrap().1;
+    let episode = Episode { title: String::from("Pilot: Part 1"), ..Episode::default() };
+    render(&tokenizer::tokenize_template(template), &TemplateValues {
+        season_no:   1,
+        episode_no:  3,
+        pad_length:  2,
+        series_name: "Marvel's Agents of S.H.I.E.L.D.",
+        series:      &Series::default(),
+        episode:     &episode,
+        release:     &Release::default(),
+        media:       &MediaInfo::default(),
+        crc32:       None,
+        part:        None
+    })
+}
+
+#[test]
+fn test_render_plex() {
+    assert_eq!(render_preset("plex"), "Marvel's Agents of S.H.I.E.L.D. - S01E03 - Pilot: Part 1");
+}
+
+#[test]
+fn test_render_kodi() {
+    assert_eq!(render_preset("kodi"), "Marvel's Agents of S.H.I.E.L.D. S01E03 Pilot: Part 1");
+}
+
+#[test]
+fn test_render_jellyfin() {
+    assert_eq!(render_preset("jellyfin"), "Marvel's Agents of S.H.I.E.L.D. S01E03 - Pilot: Part 1");
+}
+
+#[test]
+fn test_render_scene() {
+    assert_eq!(render_preset("scene"), "Marvels.Agents.of.S.H.I.E.L.D.S01E03.Pilot.Part.1");
+}
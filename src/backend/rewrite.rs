@@ -0,0 +1,84 @@
+use regex::{self, Regex};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum RewriteError {
+        Syntax(rewrite: String) {
+            display("the rewrite `{}` must be written as `PATTERN => REPLACEMENT`", rewrite)
+        }
+        Regex(err: regex::Error) {
+            display("invalid regular expression: {}", err)
+        }
+    }
+}
+
+/// A find and replace rule which is applied to metadata values, such as episode titles, before templating.
+#[derive(Debug)]
+pub struct Rewrite {
+    /// The series that the rewrite is limited to, or `None` if it applies to every series.
+    pub series:      Option<String>,
+    pub pattern:     Regex,
+    /// The replacement may refer to capture groups of the pattern, as in `$1`.
+    pub replacement: String
+}
+
+impl Rewrite {
+    fn applies_to(&self, series_names: &[&str]) -> bool {
+        self.series.as_ref().is_none_or(|series| series_names.iter().any(|name| name.eq_ignore_ascii_case(series)))
+    }
+}
+
+/// Parses a rewrite written as `PATTERN => REPLACEMENT`, such as `\s*\(\d+\)$ => `, which is scoped to the given
+/// series.
+pub fn parse_rewrite(rewrite: &str, series: Option<String>) -> Result<Rewrite, RewriteError> {
+    let mut fields = rewrite.splitn(2, "=>");
+    match (fields.next(), fields.next()) {
+        (Some(pattern), Some(replacement)) => Ok(Rewrite {
+            series:      series,
+            pattern:     Regex::new(pattern.trim()).map_err(RewriteError::Regex)?,
+            replacement: replacement.trim().to_owned()
+        }),
+        _ => Err(RewriteError::Syntax(rewrite.to_owned()))
+    }
+}
+
+/// Parses a list of rewrites, one per line. Rewrites which follow a `[Series Name]` header only apply to that
+/// series, whereas those before the first header apply to every series. Blank lines and lines beginning with `#`
+/// are ignored.
+pub fn parse_rewrites(contents: &str) -> Result<Vec<Rewrite>, RewriteError> {
+    let mut series = None;
+    let mut rewrites = Vec::new();
+    for line in contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        if line.starts_with('[') && line.ends_with(']') && !line.contains("=>") {
+            series = Some(line[1..line.len()-1].trim().to_owned());
+        } else {
+            rewrites.push(parse_rewrite(line, series.clone())?);
+        }
+    }
+    Ok(rewrites)
+}
+
+/// Applies each rewrite which is in scope for the series to the value, in order. The series may be known by more
+/// than one name, such as the name given by the user and the name recorded by the provider.
+pub fn rewrite(rewrites: &[Rewrite], series_names: &[&str], value: &str) -> String {
+    rewrites.iter()
+        .filter(|rewrite| rewrite.applies_to(series_names))
+        .fold(value.to_owned(), |value, rewrite| {
+            rewrite.pattern.replace_all(&value, rewrite.replacement.as_str()).trim().to_owned()
+        })
+}
+
+#[test]
+fn test_rewrite() {
+    let rewrites = parse_rewrites("# Global rewrites\n\
+        \\s*\\(\\d+\\)$ => \n\
+        [‘’] => '\n\
+        \n\
+        [Series Name]\n\
+        & => and").unwrap();
+    assert_eq!(rewrites.len(), 3);
+    assert_eq!(rewrite(&rewrites, &["Other"], "Pilot (1)"), "Pilot");
+    assert_eq!(rewrite(&rewrites, &["Other"], "It’s Here & Now"), "It's Here & Now");
+    assert_eq!(rewrite(&rewrites, &["series name"], "It’s Here & Now (2)"), "It's Here and Now");
+    assert!(parse_rewrite("no separator", None).is_err());
+}
@@ -0,0 +1,134 @@
+use std::path::Path;
+
+use regex::{self, Regex};
+
+use super::sonarr::{self, SonarrError};
+use super::tokenizer::TemplateToken;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum RuleError {
+        Syntax(rule: String) {
+            display("the rule `{}` must be written as `CONDITION => TEMPLATE`", rule)
+        }
+        Condition(condition: String) {
+            display("the condition `{}` is not valid", condition)
+        }
+        Regex(err: regex::Error) {
+            display("invalid regular expression: {}", err)
+        }
+        Template(err: SonarrError) {
+            display("invalid template: {}", err)
+        }
+    }
+}
+
+/// A condition that decides whether a rule applies to an episode.
+#[derive(Debug)]
+pub enum Condition {
+    /// Matches episodes within the given season, written as `season=N`.
+    Season(u8),
+    /// Matches episodes within season 0, written as `type=special`.
+    Special,
+    /// Matches episodes outside of season 0, written as `type=regular`.
+    Regular,
+    /// Matches files which contain more than one episode, such as `S01E01E02`, written as `type=multi`.
+    MultiEpisode(Regex),
+    /// Matches files with the given extension, written as `ext=mkv`.
+    Extension(String),
+    /// Matches files whose names match the regular expression, written as `regex=PATTERN`.
+    Filename(Regex),
+}
+
+/// A rule which routes the episodes that satisfy its condition to a different template.
+#[derive(Debug)]
+pub struct Rule {
+    pub condition: Condition,
+    pub template:  Vec<TemplateToken>
+}
+
+impl Condition {
+    fn parse(source: &str) -> Result<Condition, RuleError> {
+        let mut fields = source.splitn(2, '=');
+        let condition = match (fields.next().map(str::trim), fields.next().map(str::trim)) {
+            (Some("season"), Some(value)) => value.parse::<u8>().ok().map(Condition::Season),
+            (Some("type"), Some("special")) => Some(Condition::Special),
+            (Some("type"), Some("regular")) => Some(Condition::Regular),
+            (Some("type"), Some("multi")) => Some(Condition::MultiEpisode(multi_episode_pattern())),
+            (Some("ext"), Some(value)) => Some(Condition::Extension(value.trim_start_matches('.').to_lowercase())),
+            (Some("regex"), Some(value)) => Some(Condition::Filename(Regex::new(value).map_err(RuleError::Regex)?)),
+            _ => None
+        };
+        condition.ok_or_else(|| RuleError::Condition(source.to_owned()))
+    }
+
+    fn matches(&self, source: &Path, season_no: u8) -> bool {
+        let name = source.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        match *self {
+            Condition::Season(season) => season == season_no,
+            Condition::Special => season_no == 0,
+            Condition::Regular => season_no != 0,
+            Condition::MultiEpisode(ref pattern) => pattern.is_match(&name),
+            Condition::Extension(ref extension) => source.extension()
+                .is_some_and(|value| value.to_string_lossy().to_lowercase() == *extension),
+            Condition::Filename(ref regex) => regex.is_match(&name),
+        }
+    }
+}
+
+/// Files which contain multiple episodes are typically named with a range, as in `S01E01E02`, `S01E01-E02`, or
+/// `1x01-1x02`. The pattern is compiled once when the rule is parsed.
+fn multi_episode_pattern() -> Regex {
+    Regex::new(r"(?i)(s\d+e\d+(-?e\d+)+|\d+x\d+(-\d+x\d+)+)").unwrap()
+}
+
+/// Parses a rule written as `CONDITION => TEMPLATE`, such as `season=0 => Specials/${Series} - S00E${Episode}`.
+pub fn parse_rule(rule: &str) -> Result<Rule, RuleError> {
+    let mut fields = rule.splitn(2, "=>");
+    match (fields.next(), fields.next()) {
+        (Some(condition), Some(template)) => Ok(Rule {
+            condition: Condition::parse(condition.trim())?,
+            template:  sonarr::tokenize(template.trim()).map_err(RuleError::Template)?
+        }),
+        _ => Err(RuleError::Syntax(rule.to_owned()))
+    }
+}
+
+/// Parses a list of rules, one per line. Blank lines and lines beginning with `#` are ignored.
+pub fn parse_rules(contents: &str) -> Result<Vec<Rule>, RuleError> {
+    contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_rule)
+        .collect()
+}
+
+/// Evaluates the rules in order, and returns the template of the first rule whose condition is satisfied.
+pub fn select<'a>(rules: &'a [Rule], source: &Path, season_no: u8) -> Option<&'a [TemplateToken]> {
+    rules.iter().find(|rule| rule.condition.matches(source, season_no)).map(|rule| rule.template.as_slice())
+}
+
+#[test]
+fn test_select() {
+    let rules = parse_rules("# Specials\n\
+        type=special => Specials/${Series} - S00E${Episode}\n\
+        type=multi => ${Series} - ${Season}x${Episode} (Multi)\n\
+        regex=(?i)extended => ${Series} - ${Season}x${Episode} (Extended)\n\
+        ext=AVI => ${Series} - ${Season}x${Episode} (SD)").unwrap();
+    assert_eq!(rules.len(), 4);
+
+    let template = |rule: usize| Some(rules[rule].template.as_slice());
+    assert_eq!(select(&rules, Path::new("Series S00E01.mkv"), 0), template(0));
+    assert_eq!(select(&rules, Path::new("Series S01E01E02.mkv"), 1), template(1));
+    assert_eq!(select(&rules, Path::new("Series 1x01-1x02.mkv"), 1), template(1));
+    assert_eq!(select(&rules, Path::new("Series S01E01 Extended.mkv"), 1), template(2));
+    assert_eq!(select(&rules, Path::new("Series S01E01.avi"), 1), template(3));
+    assert_eq!(select(&rules, Path::new("Series S01E01.mkv"), 1), None);
+}
+
+#[test]
+fn test_parse_rule_errors() {
+    assert!(parse_rule("season=0").is_err());
+    assert!(parse_rule("season=x => ${Series}").is_err());
+    assert!(parse_rule("regex=( => ${Series}").is_err());
+}
@@ -0,0 +1,142 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// The maximum number of bytes that most filesystems permit within a single file name.
+pub const NAME_MAX: usize = 255;
+
+/// The smallest name limit that may be given, which leaves room for an episode number and an extension.
+pub const MIN_NAME_LIMIT: usize = 16;
+
+/// Defines which characters are permitted to appear within the components of a target path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Only the characters that are invalid on POSIX filesystems are replaced.
+    Posix,
+    /// Characters that are invalid on Windows, SMB, and exFAT filesystems are also replaced.
+    Windows,
+    /// The Windows policy is applied, and the result is then transliterated into ASCII.
+    Ascii,
+}
+
+impl Policy {
+    /// Obtains a policy from its name, as it would be given on the command line.
+    pub fn from_name(name: &str) -> Option<Policy> {
+        match name {
+            "posix"   => Some(Policy::Posix),
+            "windows" => Some(Policy::Windows),
+            "ascii"   => Some(Policy::Ascii),
+            _         => None
+        }
+    }
+}
+
+/// Sanitizes a single component of a path according to the given policy. The component is normalized to NFC,
+/// invalid characters are replaced, and consecutive whitespace is collapsed into a single space.
+pub fn sanitize_component(component: &str, policy: Policy) -> String {
+    let mut output = String::with_capacity(component.len());
+    for character in component.nfc() {
+        match (character, policy) {
+            ('/', _) => output.push('-'),
+            (_, Policy::Posix) => output.push(character),
+            ('\\', _) | ('|', _) => output.push('-'),
+            (':', _) => output.push_str(" -"),
+            ('"', _) => output.push('\''),
+            ('<', _) => output.push('('),
+            ('>', _) => output.push(')'),
+            ('?', _) | ('*', _) => (),
+            (_, Policy::Windows) => output.push(character),
+            (_, Policy::Ascii) => transliterate(character, &mut output)
+        }
+    }
+
+    let mut output = output.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    // Windows will silently strip trailing dots and spaces, so they are removed ahead of time.
+    if policy != Policy::Posix {
+        let length = output.trim_end_matches(['.', ' ']).len();
+        output.truncate(length);
+    }
+
+    output
+}
+
+/// Converts a character into its closest ASCII equivalent, discarding characters which have none.
+fn transliterate(character: char, output: &mut String) {
+    if character.is_ascii() {
+        output.push(character);
+        return
+    }
+
+    let replacement = match character {
+        'ß' => "ss",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'đ' | 'ð' => "d",
+        'Đ' | 'Ð' => "D",
+        'ł' => "l",
+        'Ł' => "L",
+        'þ' => "th",
+        'Þ' => "Th",
+        'ı' => "i",
+        '‘' | '’' | '‚' | '′' => "'",
+        '“' | '”' | '„' | '″' => "'",
+        '–' | '—' | '―' => "-",
+        '…' => "...",
+        '×' => "x",
+        _ => {
+            // Decompose the character and keep the base characters, dropping any accents.
+            for character in Some(character).into_iter().nfd() {
+                if character.is_ascii() && !is_combining_mark(character) { output.push(character); }
+            }
+            return
+        }
+    };
+
+    output.push_str(replacement);
+}
+
+/// Shortens a value to at most `limit` bytes, without splitting a multi-byte character.
+pub fn truncate(value: &str, limit: usize) -> &str {
+    let mut end = value.len().min(limit);
+    while !value.is_char_boundary(end) { end -= 1; }
+    value[..end].trim_end()
+}
+
+/// Shortens a title until the file name which `render` produces with it fits within `limit` bytes, so that the rest
+/// of the name is kept intact. Returns `None` if the name does not fit even without the title, or if it is empty.
+pub fn fit_title<F: FnMut(&str) -> String>(title: &str, limit: usize, mut render: F) -> Option<&str> {
+    let mut length = title.len();
+    loop {
+        let shortened = truncate(title, length);
+        let name = render(shortened);
+        if name.is_empty() { return None }
+        if name.len() <= limit { return Some(shortened) }
+        if shortened.is_empty() { return None }
+        // Sanitizing may change the length of the title, so the title is shortened by at least one byte each time.
+        length = shortened.len().saturating_sub(name.len() - limit).min(shortened.len() - 1);
+    }
+}
+
+#[test]
+fn test_sanitize_component() {
+    assert_eq!(sanitize_component("Who Are You?", Policy::Posix), "Who Are You?");
+    assert_eq!(sanitize_component("AC/DC", Policy::Posix), "AC-DC");
+    assert_eq!(sanitize_component("Who Are You?", Policy::Windows), "Who Are You");
+    assert_eq!(sanitize_component("Part 1: The \"Beginning\"...", Policy::Windows), "Part 1 - The 'Beginning'");
+    assert_eq!(sanitize_component("Cafe\u{301}   Crème", Policy::Posix), "Café Crème");
+    assert_eq!(sanitize_component("Café — Straße", Policy::Ascii), "Cafe - Strasse");
+}
+
+#[test]
+fn test_fit_title() {
+    let render = |title: &str| format!("1x01 {} [ABCD1234] - pt2.mkv", title);
+    assert_eq!(fit_title("Episode Title", 40, render), Some("Episode Title"));
+    assert_eq!(fit_title("Episode Title", 34, render), Some("Episode"));
+    assert_eq!(fit_title("ÉÉ", 28, render), Some("É"));
+    assert_eq!(fit_title("Episode Title", 20, render), None);
+    assert_eq!(fit_title("", 20, |_| String::new()), None);
+}
@@ -0,0 +1,129 @@
+use super::tokenizer::{self, TemplateToken};
+
+quick_error! {
+    #[derive(Debug, PartialEq)]
+    pub enum SonarrError {
+        Unsupported(token: String) {
+            display("the Sonarr token `{{{}}}` is not supported", token)
+        }
+        Unterminated(token: String) {
+            display("the Sonarr token `{{{}` is missing its closing `}}`", token)
+        }
+    }
+}
+
+/// Sonarr templates are written with bare braces, as in `{Series Title}`, whereas native templates always precede
+/// their braces with a `$`. Bare braces are only taken as Sonarr's when they contain a known Sonarr token, so that
+/// native templates may contain literal braces, as in `{tvdb-${TVDB_Series_Id}}`.
+pub fn is_sonarr_template(template: &str) -> bool {
+    let mut previous = ' ';
+    for (index, character) in template.char_indices() {
+        if character == '{' && previous != '$' {
+            let contents = &template[index + 1..];
+            if let Some(end) = contents.find('}') {
+                if translate_token(&contents[..end]).is_some() { return true }
+            }
+        }
+        previous = character;
+    }
+    false
+}
+
+/// Tokenizes a template, translating it from Sonarr's naming format if it is written in that format.
+pub fn tokenize(template: &str) -> Result<Vec<TemplateToken>, SonarrError> {
+    if is_sonarr_template(template) { translate(template) } else { Ok(tokenizer::tokenize_template(template)) }
+}
+
+/// Translates a template written in Sonarr's naming format, such as
+/// `{Series Title} - S{season:00}E{episode:00} - {Episode Title}`, into template tokens.
+pub fn translate(template: &str) -> Result<Vec<TemplateToken>, SonarrError> {
+    let mut tokens = Vec::new();
+    let mut characters = template.chars();
+    while let Some(character) = characters.next() {
+        if character != '{' {
+            tokens.push(TemplateToken::Character(character));
+            continue
+        }
+
+        let mut token = String::new();
+        loop {
+            match characters.next() {
+                Some('}') => break,
+                Some(character) => token.push(character),
+                None => return Err(SonarrError::Unterminated(token))
+            }
+        }
+
+        tokens.extend(translate_token(&token).ok_or(SonarrError::Unsupported(token))?);
+    }
+    Ok(tokens)
+}
+
+/// Translates a single Sonarr token, given without its braces, into the equivalent template tokens.
+fn translate_token(token: &str) -> Option<Vec<TemplateToken>> {
+    use self::TemplateToken::*;
+
+    let token = token.to_lowercase();
+
+    // Season and episode numbers are padded to the number of zeros that follow the colon.
+    let mut fields = token.splitn(2, ':');
+    match (fields.next(), fields.next()) {
+        (Some("season"), Some(pad)) if is_padding(pad) => return Some(vec![SeasonPadded(pad.len() as u8)]),
+        (Some("episode"), Some(pad)) if is_padding(pad) => return Some(vec![EpisodePadded(pad.len() as u8)]),
+        _ => ()
+    }
+
+    let tokens = match token.as_str() {
+        "season"                                 => vec![Season],
+        "episode"                                => vec![Episode],
+        "series title"                           => vec![Series],
+        "series titleyear"                       => vec![Series, Character(' '), Character('('), SeriesYear, Character(')')],
+        "series year"                            => vec![SeriesYear],
+        "episode title"                          => vec![TvdbTitle],
+        "air-date"                               => vec![TvdbFirstAired],
+        "quality title"                          => vec![Quality],
+        "quality full"                           => vec![QualityFull],
+        "mediainfo videocodec"                   => vec![VideoCodec],
+        "mediainfo audiocodec"                   => vec![AudioCodec],
+        "mediainfo audiochannels"                => vec![AudioChannels],
+        "mediainfo videodynamicrange"            => vec![Hdr],
+        "mediainfo simple"                       => vec![VideoCodec, Character(' '), AudioCodec],
+        "release group"                          => vec![Group],
+        "original title" | "original filename"   => vec![OriginalName],
+        "imdbid"                                 => vec![ImdbId],
+        "tvdbid"                                 => vec![TvdbSeriesId],
+        _                                        => return None
+    };
+    Some(tokens)
+}
+
+fn is_padding(pad: &str) -> bool {
+    !pad.is_empty() && pad.chars().all(|c| c == '0')
+}
+
+#[test]
+fn test_translate() {
+    use self::TemplateToken::*;
+
+    let tokens = translate("{Series Title} - S{season:00}E{episode:00} - {Episode Title}").unwrap();
+    assert_eq!(tokens, vec![Series, Character(' '), Character('-'), Character(' '), Character('S'),
+        SeasonPadded(2), Character('E'), EpisodePadded(2), Character(' '), Character('-'), Character(' '), TvdbTitle]);
+    assert_eq!(translate("{Series CleanTitle}"), Err(SonarrError::Unsupported("Series CleanTitle".to_owned())));
+    assert_eq!(translate("{Series Title"), Err(SonarrError::Unterminated("Series Title".to_owned())));
+}
+
+#[test]
+fn test_is_sonarr_template() {
+    assert!(is_sonarr_template("{Series Title} - {season}x{episode:00}"));
+    assert!(!is_sonarr_template("${Series} - ${Season}x${Episode}"));
+    assert!(!is_sonarr_template("${Series} {tvdb-${TVDB_Series_Id}}"));
+    assert!(!is_sonarr_template("${Series} {edition}"));
+}
+
+#[test]
+fn test_tokenize_literal_braces() {
+    use self::TemplateToken::*;
+
+    assert_eq!(tokenize("${Series} {tvdb-${TVDB_Series_Id}}").unwrap(), vec![Series, Character(' '), Character('{'),
+        Character('t'), Character('v'), Character('d'), Character('b'), Character('-'), TvdbSeriesId, Character('}')]);
+}
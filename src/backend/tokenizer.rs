@@ -6,7 +6,54 @@ pub enum TemplateToken {
     Episode,
     TvdbTitle,
     TvdbFirstAired,
+    Resolution,
+    Source,
+    Codec,
+    Group,
+    Proper,
+    Quality,
+    QualityFull,
+    OriginalName,
+    VideoHeight,
+    VideoCodec,
+    AudioCodec,
+    AudioChannels,
+    Hdr,
+    Duration,
+    SeriesYear,
+    Network,
+    TvdbSeriesId,
+    ImdbId,
+    SeriesOfficialName,
+    TvdbEpisodeId,
+    ProductionCode,
+    Rating,
+    Director,
+    Writer,
+    Runtime,
+    Crc32,
+    Part,
+    SeasonPadded(u8),
+    EpisodePadded(u8),
+    /// A value whose words are separated by dots, in the style of scene releases, as in `${Series:dots}`.
+    Dotted(Box<TemplateToken>),
+}
+
+impl TemplateToken {
+    /// Tokens whose values must be read from the headers of the media file. The runtime is also read from the
+    /// headers, but is left empty instead of failing when they cannot be read.
+    pub fn requires_media(&self) -> bool {
+        matches!(*self.value(), TemplateToken::VideoHeight | TemplateToken::VideoCodec | TemplateToken::AudioCodec
+            | TemplateToken::AudioChannels | TemplateToken::Hdr | TemplateToken::Duration)
+    }
 
+    /// The token which supplies the value, without the formatting of tokens such as `${Series:dots}`.
+    pub fn value(&self) -> &TemplateToken {
+        match *self {
+            TemplateToken::Dotted(ref token) => token.value(),
+            _ => self
+        }
+    }
 }
 
 // The default template signature is `${SERIES} ${SEASON}x${EPISODE} ${TITLE}`
@@ -24,6 +71,14 @@ pub fn default_template() -> Vec<TemplateToken> {
          TemplateToken::TvdbTitle]
 }
 
+/// Templates which are built into the application, and are selectable by name.
+pub const PRESETS: &'static [(&'static str, &'static str)] = &[
+    ("plex",     "${Series} - S${Season:02}E${Episode:02} - ${TVDB_Title}"),
+    ("kodi",     "${Series} S${Season:02}E${Episode:02} ${TVDB_Title}"),
+    ("jellyfin", "${Series} S${Season:02}E${Episode:02} - ${TVDB_Title}"),
+    ("scene",    "${Series:dots}.S${Season:02}E${Episode:02}.${TVDB_Title:dots}"),
+];
+
 /// This tokenizer will take the template string as input and convert it into an ordered vector of tokens.
 pub fn tokenize_template(template: &str) -> Vec<TemplateToken> {
     let mut tokens = Vec::new();
@@ -85,7 +140,107 @@ fn match_token(pattern: &str) -> Option<TemplateToken> {
         "${Episode}"          => Some(TemplateToken::Episode),
         "${TVDB_Title}"       => Some(TemplateToken::TvdbTitle),
         "${TVDB_First_Aired}" => Some(TemplateToken::TvdbFirstAired),
-        _                     => None
+        "${Resolution}"       => Some(TemplateToken::Resolution),
+        "${Source}"           => Some(TemplateToken::Source),
+        "${Codec}"            => Some(TemplateToken::Codec),
+        "${Group}"            => Some(TemplateToken::Group),
+        "${Proper}"           => Some(TemplateToken::Proper),
+        "${Quality}"          => Some(TemplateToken::Quality),
+        "${Quality_Full}"     => Some(TemplateToken::QualityFull),
+        "${Original_Name}"    => Some(TemplateToken::OriginalName),
+        "${Video_Height}"     => Some(TemplateToken::VideoHeight),
+        "${Video_Codec}"      => Some(TemplateToken::VideoCodec),
+        "${Audio_Codec}"      => Some(TemplateToken::AudioCodec),
+        "${Audio_Channels}"   => Some(TemplateToken::AudioChannels),
+        "${HDR}"              => Some(TemplateToken::Hdr),
+        "${Duration}"         => Some(TemplateToken::Duration),
+        "${Series_Year}"      => Some(TemplateToken::SeriesYear),
+        "${Network}"          => Some(TemplateToken::Network),
+        "${TVDB_Series_Id}"   => Some(TemplateToken::TvdbSeriesId),
+        "${IMDb_Id}"          => Some(TemplateToken::ImdbId),
+        "${Series_Official_Name}" => Some(TemplateToken::SeriesOfficialName),
+        "${TVDB_Episode_Id}"  => Some(TemplateToken::TvdbEpisodeId),
+        "${Production_Code}"  => Some(TemplateToken::ProductionCode),
+        "${Rating}"           => Some(TemplateToken::Rating),
+        "${Director}"         => Some(TemplateToken::Director),
+        "${Writer}"           => Some(TemplateToken::Writer),
+        "${Runtime}"          => Some(TemplateToken::Runtime),
+        "${CRC32}"            => Some(TemplateToken::Crc32),
+        "${Part}"             => Some(TemplateToken::Part),
+        _                     => match_padded_token(pattern)
+    }
+}
+
+/// Matches season and episode tokens that specify how many digits to pad to, such as `${Season:02}`, and tokens
+/// whose words are separated by dots, such as `${Series:dots}`.
+fn match_padded_token(pattern: &str) -> Option<TemplateToken> {
+    let mut fields = pattern.trim_start_matches("${").trim_end_matches('}').splitn(2, ':');
+    let (name, modifier) = (fields.next(), fields.next());
+    if let (Some(name), Some("dots")) = (name, modifier) {
+        return match_token(&format!("${{{}}}", name)).map(|token| TemplateToken::Dotted(Box::new(token)));
+    }
+    match (name, modifier.and_then(|pad| pad.parse::<u8>().ok())) {
+        (Some("Season"), Some(pad))  => Some(TemplateToken::SeasonPadded(pad)),
+        (Some("Episode"), Some(pad)) => Some(TemplateToken::EpisodePadded(pad)),
+        _                            => None
+    }
+}
+
+/// Uses a template as a pattern to recover the season and episode numbers from a file name that was previously
+/// produced by the template. The file name should be given without its extension or parent directories, and any
+/// directories described by the template are ignored. Returns `None` if the name does not match the template, or
+/// if the template does not contain an episode number.
+pub fn parse_filename(template: &[TemplateToken], name: &str) -> Option<(Option<u8>, u16)> {
+    let start = template.iter().rposition(|token| *token == TemplateToken::Character('/')).map_or(0, |i| i + 1);
+    match match_name(&template[start..], name, None, None) {
+        Some((season, Some(episode))) if season.is_none_or(|s| s <= 255) && episode <= 65535 => {
+            Some((season.map(|s| s as u8), episode as u16))
+        },
+        _ => None
+    }
+}
+
+/// Matches the remaining tokens against the remaining input, backtracking over every possible length of each
+/// variable token until a complete match is found.
+fn match_name(tokens: &[TemplateToken], input: &str, season: Option<u32>, episode: Option<u32>)
+    -> Option<(Option<u32>, Option<u32>)>
+{
+    let (token, rest) = match tokens.split_first() {
+        Some(value) => value,
+        None => return if input.is_empty() { Some((season, episode)) } else { None }
+    };
+
+    match *token {
+        TemplateToken::Character(character) => if input.starts_with(character) {
+            match_name(rest, &input[character.len_utf8()..], season, episode)
+        } else {
+            None
+        },
+        TemplateToken::Season | TemplateToken::SeasonPadded(_) | TemplateToken::Episode
+            | TemplateToken::EpisodePadded(_) =>
+        {
+            let is_season = matches!(*token, TemplateToken::Season | TemplateToken::SeasonPadded(_));
+            let digits = input.chars().take_while(|c| c.is_ascii_digit()).count().min(9);
+            for length in (1..digits + 1).rev() {
+                let value = input[..length].parse::<u32>().ok();
+                // A number which appears more than once within the template must have the same value each time.
+                let (season, episode) = if is_season {
+                    if season.is_some() && season != value { continue }
+                    (value, episode)
+                } else {
+                    if episode.is_some() && episode != value { continue }
+                    (season, value)
+                };
+                if let Some(result) = match_name(rest, &input[length..], season, episode) { return Some(result) }
+            }
+            None
+        },
+        _ => {
+            for (index, _) in input.char_indices().chain(Some((input.len(), ' '))) {
+                if let Some(result) = match_name(rest, &input[index..], season, episode) { return Some(result) }
+            }
+            None
+        }
     }
 }
 
@@ -94,6 +249,22 @@ fn test_tokenize() {
     assert_eq!(default_template(), tokenize_template("${Series} - ${Season}x${Episode} - ${TVDB_Title}"));
 }
 
+#[test]
+fn test_parse_filename() {
+    let template = tokenize_template("${Series}/Season ${Season}/${Series} - ${Season}x${Episode} - ${TVDB_Title}");
+    assert_eq!(parse_filename(&template, "24 - 3x07 - Day 3: 7:00 P.M.-8:00 P.M."), Some((Some(3), 7)));
+    assert_eq!(parse_filename(&template, "24 - 3x07"), None);
+    assert_eq!(parse_filename(&default_template(), "one"), None);
+    assert_eq!(parse_filename(&tokenize_template("${Series} E${Episode}"), "Series E12"), Some((None, 12)));
+}
+
+#[test]
+fn test_presets() {
+    for &(_, template) in PRESETS {
+        assert!(!tokenize_template(template).contains(&TemplateToken::Character('$')));
+    }
+}
+
 #[test]
 fn test_match_token() {
     assert_eq!(Some(TemplateToken::Series), match_token("${Series}"));
@@ -101,5 +272,21 @@ fn test_match_token() {
     assert_eq!(Some(TemplateToken::Episode), match_token("${Episode}"));
     assert_eq!(Some(TemplateToken::TvdbTitle), match_token("${TVDB_Title}"));
     assert_eq!(Some(TemplateToken::TvdbFirstAired), match_token("${TVDB_First_Aired}"));
+    assert_eq!(Some(TemplateToken::Resolution), match_token("${Resolution}"));
+    assert_eq!(Some(TemplateToken::QualityFull), match_token("${Quality_Full}"));
+    assert_eq!(Some(TemplateToken::OriginalName), match_token("${Original_Name}"));
+    assert_eq!(Some(TemplateToken::VideoHeight), match_token("${Video_Height}"));
+    assert_eq!(Some(TemplateToken::Hdr), match_token("${HDR}"));
+    assert_eq!(Some(TemplateToken::SeriesYear), match_token("${Series_Year}"));
+    assert_eq!(Some(TemplateToken::TvdbSeriesId), match_token("${TVDB_Series_Id}"));
+    assert_eq!(Some(TemplateToken::TvdbEpisodeId), match_token("${TVDB_Episode_Id}"));
+    assert_eq!(Some(TemplateToken::Runtime), match_token("${Runtime}"));
+    assert!(!TemplateToken::Runtime.requires_media() && TemplateToken::Duration.requires_media());
+    assert_eq!(Some(TemplateToken::Crc32), match_token("${CRC32}"));
+    assert_eq!(Some(TemplateToken::Part), match_token("${Part}"));
+    assert_eq!(Some(TemplateToken::SeasonPadded(2)), match_token("${Season:02}"));
+    assert_eq!(Some(TemplateToken::EpisodePadded(3)), match_token("${Episode:3}"));
+    assert_eq!(Some(TemplateToken::Dotted(Box::new(TemplateToken::Series))), match_token("${Series:dots}"));
+    assert_eq!(None, match_token("${Season:}"));
     assert_eq!(None, match_token("${invalid}"));
 }
@@ -0,0 +1,359 @@
+use std::env;
+#[cfg(unix)]
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::os::unix::fs::{symlink, MetadataExt};
+#[cfg(windows)]
+use std::os::windows::fs::symlink_file as symlink;
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::ptr;
+#[cfg(not(unix))]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use libc;
+
+use super::checksum;
+
+/// Determines how an episode is transferred from its source to its target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferMode {
+    /// The episode is renamed, which requires the source and target to be on the same filesystem.
+    Rename,
+    /// The episode is renamed, or copied along with its metadata and then deleted if the target is on a different
+    /// filesystem.
+    Move,
+    /// The episode is copied along with its metadata, and the source is kept.
+    Copy,
+    /// A hard link to the episode is created, and the source is kept.
+    Hardlink,
+    /// A symbolic link to the episode is created, and the source is kept.
+    Symlink,
+    /// A copy-on-write clone of the episode is created on filesystems which support it, along with its metadata,
+    /// and the source is kept.
+    Reflink
+}
+
+/// Every transfer mode, in the order that they are offered to the user.
+pub const TRANSFER_MODES: [TransferMode; 6] = [TransferMode::Rename, TransferMode::Move, TransferMode::Copy,
+    TransferMode::Hardlink, TransferMode::Symlink, TransferMode::Reflink];
+
+impl TransferMode {
+    pub fn from_name(name: &str) -> Option<TransferMode> {
+        TRANSFER_MODES.iter().cloned().find(|mode| mode.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TransferMode::Rename   => "rename",
+            TransferMode::Move     => "move",
+            TransferMode::Copy     => "copy",
+            TransferMode::Hardlink => "hardlink",
+            TransferMode::Symlink  => "symlink",
+            TransferMode::Reflink  => "reflink"
+        }
+    }
+
+    /// Whether the source no longer exists once it has been transferred, so that its name becomes free.
+    pub fn removes_source(self) -> bool {
+        self == TransferMode::Rename || self == TransferMode::Move
+    }
+
+    /// Whether the target is the original file, rather than a separate copy or a link to it, so that changing the
+    /// metadata of the target changes the original.
+    pub fn shares_file(self) -> bool {
+        self.removes_source() || self == TransferMode::Hardlink
+    }
+
+    /// Whether the mode can be performed on this platform. Symbolic links require Unix or Windows, and reflinks
+    /// are only supported on Linux.
+    pub fn is_supported(self) -> bool {
+        match self {
+            TransferMode::Symlink => cfg!(any(unix, windows)),
+            TransferMode::Reflink => cfg!(target_os = "linux"),
+            _                     => true
+        }
+    }
+}
+
+/// The number of bytes that are copied at a time, and thus between each report of the progress of a copy.
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Copies the contents of the source into the target, reporting the number of bytes that have been copied and the
+/// size of the source after each block. The space for the copy is allocated up front, and the copy is flushed to
+/// the disk before returning. A partial copy is removed if the copy fails.
+fn copy_file<F: FnMut(u64, u64)>(source: &Path, target: &Path, mut progress: F) -> io::Result<()> {
+    let mut input = File::open(source)?;
+    let total = input.metadata()?.len();
+    let mut output = File::create(target)?;
+    let mut copy = || {
+        output.set_len(total)?;
+        let mut buffer = vec![0u8; BLOCK_SIZE];
+        let mut copied = 0;
+        loop {
+            let read = match input.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(ref why) if why.kind() == io::ErrorKind::Interrupted => continue,
+                Err(why) => return Err(why)
+            };
+            output.write_all(&buffer[..read])?;
+            copied += read as u64;
+            progress(copied, total);
+        }
+        // The source may have shrunk while it was being copied.
+        if copied != total { output.set_len(copied)?; }
+        output.sync_all()
+    };
+    copy().inspect_err(|_| {
+        let _ = fs::remove_file(target);
+    })
+}
+
+/// Copies the source into the target, and then compares the checksums of both files, so that the source may be
+/// safely deleted afterwards. The target is removed if it does not match the source.
+fn copy_verified<F: FnMut(u64, u64)>(source: &Path, target: &Path, progress: F) -> io::Result<()> {
+    copy_file(source, target, progress)?;
+    if checksum::crc32_file(source)? != checksum::crc32_file(target)? {
+        let _ = fs::remove_file(target);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the copy does not match the original"));
+    }
+    Ok(())
+}
+
+/// Clones the source into the target with the `FICLONE` ioctl, which shares the extents of the source until either
+/// file is modified.
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    // Defined as `_IOW(0x94, 9, int)` in linux/fs.h.
+    const FICLONE: u64 = 0x4004_9409;
+
+    let input = File::open(source)?;
+    let output = File::create(target)?;
+    if unsafe { libc::ioctl(output.as_raw_fd(), FICLONE as _, input.as_raw_fd()) } == -1 {
+        let why = io::Error::last_os_error();
+        let _ = fs::remove_file(target);
+        return Err(why);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_source: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "reflinks are not supported on this platform"))
+}
+
+/// Converts a path into a C string for the system calls which are not wrapped by the standard library.
+#[cfg(unix)]
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the path contains a null byte"))
+}
+
+/// Sets the access and modification times of a path as seconds and nanoseconds since the Unix epoch, where an
+/// access time of `None` keeps the current time. The times of a symbolic link are set on the link itself.
+#[cfg(unix)]
+fn set_times(path: &Path, accessed: Option<(i64, i64)>, modified: (i64, i64)) -> io::Result<()> {
+    let path = c_path(path)?;
+    let accessed = match accessed {
+        Some((seconds, nanoseconds)) => libc::timespec { tv_sec: seconds as _, tv_nsec: nanoseconds as _ },
+        None => libc::timespec { tv_sec: 0, tv_nsec: libc::UTIME_OMIT }
+    };
+    let times = [accessed, libc::timespec { tv_sec: modified.0 as _, tv_nsec: modified.1 as _ }];
+    if unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), libc::AT_SYMLINK_NOFOLLOW) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Converts a time in seconds since the Unix epoch, which may precede it, into a system time.
+#[cfg(not(unix))]
+fn system_time(seconds: i64) -> SystemTime {
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    }
+}
+
+/// Reads the modification time of a path in seconds since the Unix epoch, without following symbolic links.
+#[cfg(unix)]
+pub fn modified(path: &Path) -> io::Result<i64> {
+    fs::symlink_metadata(path).map(|metadata| metadata.mtime())
+}
+
+#[cfg(not(unix))]
+pub fn modified(path: &Path) -> io::Result<i64> {
+    let modified = fs::symlink_metadata(path)?.modified()?;
+    Ok(match modified.duration_since(UNIX_EPOCH) {
+        Ok(time) => time.as_secs() as i64,
+        Err(why) => -(why.duration().as_secs() as i64)
+    })
+}
+
+/// Sets the modification time of a path to the given number of seconds since the Unix epoch, keeping its access
+/// time. The time of a symbolic link is set on the link, rather than on the episode that it points to.
+#[cfg(unix)]
+pub fn set_modified(path: &Path, seconds: i64) -> io::Result<()> {
+    set_times(path, None, (seconds, 0))
+}
+
+/// Other platforms set the time through the file, so the time of a symbolic link is set on the episode.
+#[cfg(not(unix))]
+pub fn set_modified(path: &Path, seconds: i64) -> io::Result<()> {
+    fs::OpenOptions::new().write(true).open(path)?.set_modified(system_time(seconds))
+}
+
+/// Gives the target the access and modification times of the source.
+#[cfg(unix)]
+fn copy_times(source: &fs::Metadata, target: &Path) -> io::Result<()> {
+    set_times(target, Some((source.atime(), source.atime_nsec())), (source.mtime(), source.mtime_nsec()))
+}
+
+#[cfg(not(unix))]
+fn copy_times(source: &fs::Metadata, target: &Path) -> io::Result<()> {
+    let times = fs::FileTimes::new().set_accessed(source.accessed()?).set_modified(source.modified()?);
+    fs::OpenOptions::new().write(true).open(target)?.set_times(times)
+}
+
+/// Copies the extended attributes within the `user` namespace from the source to the target. Attributes are
+/// silently dropped when either filesystem does not support them, and on platforms other than Linux.
+#[cfg(target_os = "linux")]
+fn copy_xattrs(source: &Path, target: &Path) -> io::Result<()> {
+    let unsupported = |why: io::Error| if why.raw_os_error() == Some(libc::ENOTSUP) { Ok(()) } else { Err(why) };
+    let (source, target) = (c_path(source)?, c_path(target)?);
+
+    // The names are listed as a sequence of null-terminated strings, after querying the size of the list.
+    let size = unsafe { libc::llistxattr(source.as_ptr(), ptr::null_mut(), 0) };
+    if size == -1 { return unsupported(io::Error::last_os_error()) }
+    let mut names = vec![0u8; size as usize];
+    let size = unsafe { libc::llistxattr(source.as_ptr(), names.as_mut_ptr() as *mut libc::c_char, names.len()) };
+    if size == -1 { return unsupported(io::Error::last_os_error()) }
+    names.truncate(size as usize);
+
+    for name in names.split(|&byte| byte == 0).filter(|name| name.starts_with(b"user.")) {
+        let name = CString::new(name).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid name"))?;
+        let size = unsafe { libc::lgetxattr(source.as_ptr(), name.as_ptr(), ptr::null_mut(), 0) };
+        if size == -1 { return Err(io::Error::last_os_error()) }
+        let mut value = vec![0u8; size as usize];
+        let size = unsafe {
+            libc::lgetxattr(source.as_ptr(), name.as_ptr(), value.as_mut_ptr() as *mut libc::c_void, value.len())
+        };
+        if size == -1 { return Err(io::Error::last_os_error()) }
+        let set = unsafe {
+            libc::lsetxattr(target.as_ptr(), name.as_ptr(), value.as_ptr() as *const libc::c_void, size as usize, 0)
+        };
+        if set == -1 { return unsupported(io::Error::last_os_error()) }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_xattrs(_source: &Path, _target: &Path) -> io::Result<()> { Ok(()) }
+
+/// Gives the target the user extended attributes, permissions, and access and modification times of the source,
+/// which are lost when the contents of an episode are copied rather than renamed or linked. The target is removed
+/// if they could not be preserved, so that a copy is not left behind without them.
+fn preserve_metadata(source: &Path, target: &Path) -> io::Result<()> {
+    let preserve = || {
+        let metadata = fs::metadata(source)?;
+        // Attributes are copied first, as they cannot be written once the target is given read-only permissions.
+        copy_xattrs(source, target)?;
+        // Times are set before permissions, as a read-only file cannot be opened to set them on some platforms.
+        copy_times(&metadata, target)?;
+        fs::set_permissions(target, metadata.permissions())
+    };
+    preserve().inspect_err(|_| {
+        let _ = fs::remove_file(target);
+    })
+}
+
+/// The error which is returned when a file cannot be renamed onto a different filesystem.
+#[cfg(unix)]
+const CROSS_DEVICE: i32 = libc::EXDEV;
+/// `ERROR_NOT_SAME_DEVICE`, which is returned when a file cannot be moved to a different drive.
+#[cfg(windows)]
+const CROSS_DEVICE: i32 = 17;
+#[cfg(not(any(unix, windows)))]
+const CROSS_DEVICE: i32 = -1;
+
+/// Symbolic links are not supported on platforms other than Unix and Windows.
+#[cfg(not(any(unix, windows)))]
+fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(_source: P, _target: Q) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "symbolic links are not supported on this platform"))
+}
+
+/// Transfers the source to the target according to the mode, creating the directories of the target as needed.
+/// The progress of copies is reported as the number of bytes copied and the size of the source.
+pub fn transfer<F>(source: &Path, target: &Path, mode: TransferMode, progress: F) -> io::Result<()>
+    where F: FnMut(u64, u64)
+{
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match mode {
+        TransferMode::Rename => fs::rename(source, target),
+        TransferMode::Move => match fs::rename(source, target) {
+            // The target is on a different filesystem, so the source must be copied and then deleted.
+            Err(ref why) if why.raw_os_error() == Some(CROSS_DEVICE) => {
+                copy_verified(source, target, progress)?;
+                preserve_metadata(source, target)?;
+                fs::remove_file(source)
+            },
+            result => result
+        },
+        TransferMode::Copy => copy_file(source, target, progress)
+            .and_then(|_| preserve_metadata(source, target)),
+        TransferMode::Hardlink => fs::hard_link(source, target),
+        // Symbolic links point to an absolute path, so that they remain valid wherever the target is placed.
+        TransferMode::Symlink => symlink(env::current_dir()?.join(source), target),
+        TransferMode::Reflink => reflink(source, target).and_then(|_| preserve_metadata(source, target))
+    }
+}
+
+/// Reverts a transfer, by moving the target back to its source, or by removing the target if the source was kept.
+pub fn revert(source: &Path, target: &Path, mode: TransferMode) -> io::Result<()> {
+    if mode.removes_source() {
+        transfer(target, source, mode, |_, _| ())
+    } else {
+        fs::remove_file(target)
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_transfer() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory = super::TempDir::new("transfer");
+    File::create(directory.join("source.mkv")).unwrap().write_all(b"episode").unwrap();
+    fs::set_permissions(directory.join("source.mkv"), fs::Permissions::from_mode(0o640)).unwrap();
+    set_modified(&directory.join("source.mkv"), 1_000_000_000).unwrap();
+
+    let mut reported = 0;
+    transfer(&directory.join("source.mkv"), &directory.join("copy/1x01.mkv"), TransferMode::Copy,
+        |copied, total| { assert_eq!(total, 7); reported = copied; }).unwrap();
+    assert_eq!(reported, 7);
+    let copied = fs::metadata(directory.join("copy/1x01.mkv")).unwrap();
+    assert_eq!((copied.mtime(), copied.permissions().mode() & 0o777), (1_000_000_000, 0o640));
+    transfer(&directory.join("source.mkv"), &directory.join("1x01.mkv"), TransferMode::Symlink, |_, _| ()).unwrap();
+    assert_eq!(fs::read_link(directory.join("1x01.mkv")).unwrap(), directory.join("source.mkv"));
+    // An existing target is never replaced by a transfer, as it is moved aside before a plan is applied.
+    assert!(transfer(&directory.join("source.mkv"), &directory.join("1x01.mkv"), TransferMode::Hardlink, |_, _| ())
+        .is_err());
+    revert(&directory.join("source.mkv"), &directory.join("1x01.mkv"), TransferMode::Symlink).unwrap();
+    transfer(&directory.join("source.mkv"), &directory.join("1x01.mkv"), TransferMode::Hardlink, |_, _| ()).unwrap();
+    assert!(fs::symlink_metadata(directory.join("1x01.mkv")).unwrap().file_type().is_file());
+
+    revert(&directory.join("source.mkv"), &directory.join("1x01.mkv"), TransferMode::Hardlink).unwrap();
+    assert!(directory.join("source.mkv").exists() && !directory.join("1x01.mkv").exists());
+    assert_eq!(checksum::crc32_file(&directory.join("copy/1x01.mkv")).unwrap(),
+        checksum::crc32_file(&directory.join("source.mkv")).unwrap());
+}
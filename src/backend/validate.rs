@@ -0,0 +1,63 @@
+use std::path::{Component, Path, PathBuf};
+
+quick_error! {
+    #[derive(Debug, PartialEq)]
+    pub enum UnsafeTarget {
+        OutsideRoot(target: PathBuf) {
+            display("{:?} is outside of the output directory", target)
+        }
+        Traversal(target: PathBuf) {
+            display("{:?} contains a relative path component", target)
+        }
+        ControlCharacter(component: String) {
+            display("{:?} contains a control character", component)
+        }
+        ReservedName(component: String) {
+            display("{:?} is a reserved file name", component)
+        }
+    }
+}
+
+/// Names which Windows reserves for devices, regardless of the extension that follows them.
+const RESERVED_NAMES: &'static [&'static str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Ensures that a computed target is located within the given root directory, and that none of the components
+/// beneath the root contain control characters or reserved names.
+pub fn validate_target(root: &Path, target: &Path) -> Result<(), UnsafeTarget> {
+    let relative = target.strip_prefix(root).map_err(|_| UnsafeTarget::OutsideRoot(target.to_path_buf()))?;
+
+    for component in relative.components() {
+        let component = match component {
+            Component::Normal(component) => component.to_string_lossy(),
+            _ => return Err(UnsafeTarget::Traversal(target.to_path_buf()))
+        };
+
+        if component.chars().any(char::is_control) {
+            return Err(UnsafeTarget::ControlCharacter(component.into_owned()));
+        }
+
+        let stem = component.split('.').next().unwrap_or("").trim_end().to_uppercase();
+        if RESERVED_NAMES.contains(&stem.as_str()) {
+            return Err(UnsafeTarget::ReservedName(component.into_owned()));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_target() {
+    let root = Path::new("/media/Series");
+    assert_eq!(validate_target(root, &root.join("Season 1/Series - 1x01.mkv")), Ok(()));
+    assert_eq!(validate_target(root, &root.join("../1x01.mkv")),
+        Err(UnsafeTarget::Traversal(root.join("../1x01.mkv"))));
+    assert_eq!(validate_target(root, Path::new("/media/1x01.mkv")),
+        Err(UnsafeTarget::OutsideRoot(PathBuf::from("/media/1x01.mkv"))));
+    assert_eq!(validate_target(root, &root.join("1x01 \u{7}.mkv")),
+        Err(UnsafeTarget::ControlCharacter("1x01 \u{7}.mkv".to_owned())));
+    assert_eq!(validate_target(root, &root.join("Con.mkv")), Err(UnsafeTarget::ReservedName("Con.mkv".to_owned())));
+}
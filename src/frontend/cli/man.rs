@@ -6,8 +6,23 @@ SYNOPSIS:
                          [-n | --series-name "NAME OF SERIES"]
                          [-s | --season-number NUMBER]
                          [-t | --template "TEMPLATE"]
+                         [-P | --preset plex|kodi|jellyfin|scene|NAME]
+                         [--season-template "TEMPLATE"]
+                         [--series-template "TEMPLATE"]
+                         [--rule "CONDITION => TEMPLATE"]...
+                         [--rewrite "PATTERN => REPLACEMENT"]...
                          [-p | --pad-length NUMBER]
                          [-e | --episode-start NUMBER]
+                         [-o | --output-directory DIRECTORY]
+                         [-r | --remove-empty]
+                         [-S | --sanitize posix|windows|ascii]
+                         [-l | --name-limit BYTES]
+                         [--on-conflict skip|overwrite|suffix|abort|ask]
+                         [--transfer rename|move|copy|hardlink|symlink|reflink]
+                         [--mtime-from-air-date]
+                         [-c | --verify]
+                         [-k | --keep-going]
+    tv-renamer undo [--last | --id ID | --list]
 
 DESCRIPTION:
     Renames all videos in a directory according to their season and episode.
@@ -18,7 +33,13 @@ DESCRIPTION:
 
     It is recommended to use the dry-run option first before committing any changes.
 
-    If a target file already exists, the command will ask if it is okay to overwrite the file.
+    If a target file already exists, the --on-conflict policy decides what happens.
+
+    The new name of every episode in every season is computed and checked before anything is renamed, and every
+    problem that would cause a rename to fail, such as a directory which is not writable, a name which is longer
+    than 255 bytes, two episodes with the same new name, or an episode which no longer exists, is reported at
+    once. Nothing is renamed if any problem is found, and if a rename fails, the episodes in the season which
+    were already renamed are reverted in reverse order, unless --keep-going is given.
 
     Please ensure that all of the files in the directory are video files that you want renamed.
 
@@ -35,16 +56,156 @@ OPTIONS:
     -t, --template:
         Sets the template that will define the naming scheme.
         [default: "${Series} - ${Season}x${Episode} - ${TVDB_Title}"]
+        Templates written in Sonarr's naming format, such as "{Series Title} - S{season:00}E{episode:00}", are
+        also accepted, and any Sonarr token which is not supported will be reported as an error. Braces
+        which do not contain a known Sonarr token are kept as literal text.
+        Files which were previously renamed with the same template keep the season and episode numbers in their
+        names, rather than their alphabetical order, so renaming a directory again only updates stale titles.
+        Episodes which are split across multiple files, such as ep01.cd1.avi and ep01.cd2.avi, share the same
+        episode number. The part is substituted into the ${Part} token as pt1, pt2, and so on, or is appended to
+        the name as " - pt1" if the template does not contain that token.
+
+    --season-template:
+        When the directory contains season directories, renames each season directory with this template after
+        its episodes have been renamed, such as "Season ${Season:02}". Season 0 is always named "Specials".
+
+    --series-template:
+        When the directory contains season directories, renames the series directory with this template after
+        the season directories have been renamed, such as "${Series} (${Series_Year})". The new directory names
+        are checked along with the episodes, so that a directory which already exists, or which more than one
+        directory would be renamed to, is reported before anything is renamed. If a directory cannot be renamed,
+        the directories which were already renamed are reverted, and the episodes are left renamed within their
+        original directories.
+
+    -P, --preset:
+        Sets the template to a named preset. The built-in presets are:
+            plex:     ${Series} - S${Season:02}E${Episode:02} - ${TVDB_Title}
+            kodi:     ${Series} S${Season:02}E${Episode:02} ${TVDB_Title}
+            jellyfin: ${Series} S${Season:02}E${Episode:02} - ${TVDB_Title}
+            scene:    ${Series:dots}.S${Season:02}E${Episode:02}.${TVDB_Title:dots}
+        Additional presets may be defined in ~/.config/tv-renamer/presets, one per line:
+            name = ${Series} ${Season}x${Episode}
+
+    --rule:
+        Routes the episodes which satisfy a condition to a different template. Rules are evaluated in order, and
+        the first rule whose condition is satisfied replaces the template for that episode. This option may be
+        given more than once, and is evaluated before the rules in ~/.config/tv-renamer/rules, one per line:
+            season=0 => Specials/${Series} - S00E${Episode:02} - ${TVDB_Title}
+        The available conditions are season=NUMBER, type=special, type=regular, type=multi for files which
+        contain more than one episode, ext=EXTENSION, and regex=PATTERN, which is matched against the file name.
+
+    --rewrite:
+        Rewrites metadata values, such as episode titles, with a regular expression before they are templated.
+        Replacements may refer to capture groups as $1. This option may be given more than once, and is applied
+        before the rewrites in ~/.config/tv-renamer/rewrites. Rewrites in that file which follow a [Series Name]
+        header only apply to that series:
+            \s*\(\d+\)$ =>
+            [‘’] => '
+            [Series Name]
+            & => and
+        The dry-run and verbose options print the original and rewritten title of each episode.
 
     -e, --episode-start:
         Sets the episode number to start counting from. [default: 1]
+        When given, episodes are always numbered in alphabetical order from this number, even if their
+        names already match the template.
 
     -p, --pad-length:
         Sets the number of digits to pad the episode count for. [default: 2]
 
+    -o, --output-directory:
+        Sets the root directory that renamed episodes will be moved into.
+        [default: the directory of each episode]
+
+    -r, --remove-empty:
+        Removes source directories that are left empty after renaming. Only the directories which contained a
+        moved episode, and their parents within DIRECTORY, are removed, so directories which were already empty
+        and symbolic links are left in place.
+
+    -S, --sanitize:
+        Sets which characters are permitted in file names. [default: posix]
+            posix:   only `/` is replaced.
+            windows: `\ : * ? " < > |` and trailing dots are also replaced, for SMB and exFAT drives.
+            ascii:   the windows policy, with all other characters transliterated into ASCII.
+        Every policy normalizes names to Unicode NFC and collapses consecutive whitespace.
+
+    -l, --name-limit:
+        Sets the maximum length of a file name in bytes, between 16 and 255. When a name is too long, only the
+        episode title is shortened, so the rest of the name, the part suffix, and the extension are kept. An
+        episode whose name is too long even without its title is reported as an error. [default: 255]
+
+    --on-conflict:
+        Sets what happens when a file already exists at the target of an episode. [default: ask]
+            skip:      the episode is left as it is, and is reported as skipped.
+            overwrite: the existing file is replaced. It is kept under a temporary name until the season
+                       has been renamed, and is restored if the season is reverted.
+            suffix:    the episode is renamed to the first free name, such as "Title (2).mkv".
+            abort:     nothing in the season is renamed.
+            ask:       asks which of the above to do for each conflict.
+        When more than one episode would be renamed to the same target, skip keeps only the first episode, suffix
+        keeps every episode, and overwrite and abort rename nothing in the season, while ask lists the size and
+        quality of each episode and asks which to keep. Targets which are currently occupied by another episode
+        that is being renamed, such as when episodes are shifted by one or two episodes are swapped, are not
+        considered to exist, as those episodes are moved aside to temporary names first.
+
+    --transfer:
+        Sets how each episode is transferred to its new name. [default: rename]
+            rename:   the episode is renamed, which requires the target to be on the same filesystem.
+            move:     the episode is renamed, or is copied, flushed to disk, verified, and then deleted when the
+                      target is on a different filesystem.
+            copy:     the episode is copied, and the original is kept.
+            hardlink: a hard link to the episode is created, and the original is kept.
+            symlink:  a symbolic link to the episode is created, and the original is kept. Only supported on
+                      Unix and Windows.
+            reflink:  a copy-on-write clone of the episode is created on filesystems which support it, such as
+                      Btrfs and XFS, and the original is kept. Only supported on Linux.
+        Modes which are not supported on the platform are rejected. Copies keep the permissions, access and
+        modification times of the original, along with its user extended attributes on Linux, while renames and
+        links share them with the original. The progress of copies of large files is shown when running in a
+        terminal on Unix. Season and series directories are not renamed when the originals are kept, and undoing
+        such a run removes the episodes that were created, provided that the originals are unchanged.
+
+    --mtime-from-air-date:
+        Sets the modification time of each renamed episode to noon UTC on the date that it first aired, so that
+        file managers sort episodes by their air date. Episodes without a known air date keep their time. As
+        renames and hard links share their time with the original, the original's time is changed as well, and
+        its previous time is restored when the season is reverted or the run is undone.
+
+    -c, --verify:
+        Compares the CRC32 checksum embedded within each file name, such as [ABCD1234], against the contents of
+        the file. If any checksum does not match, the mismatches are reported and nothing is renamed.
+
+    -k, --keep-going:
+        Records the episodes which cannot be renamed and continues with the rest, instead of renaming nothing.
+        Episodes which cannot be found or which fail the checks before renaming are left in place, and a season
+        whose conflicts are aborted, or whose renames fail and are reverted, does not stop the other seasons.
+        A summary table of the renamed, unchanged, skipped, and failed episodes, along with the reason each
+        episode failed, is printed at the end. The episodes of a season which were reverted are counted as
+        failed, with the reason "rolled back". The exit status is 2 if some episodes were renamed but others
+        failed.
+
     -v, --verbose:
         Print the changes that are occurring.
 
+EXIT STATUS:
+    0:  every episode was renamed, or was already named correctly.
+    1:  nothing was renamed, because of an error.
+    2:  some episodes were renamed, but others failed, such as with --keep-going.
+
+UNDO OPTIONS:
+    Every rename which is applied is recorded in a journal in ~/.local/share/tv-renamer/journal, and runs which
+    were recorded in the journal are reverted with `tv-renamer undo`. Nothing is reverted if any of the renamed
+    files have been modified, moved, or replaced since they were renamed.
+
+    --last:
+        Reverts the most recent run that was recorded in the journal. [default]
+
+    --id:
+        Reverts the run with the given journal ID.
+
+    --list:
+        Lists the ID, series, number of renames, and time of every run that was recorded in the journal.
+
 EXAMPLE:
     When executed inside of a directory with the name of the TV Series
         > one.mkv two.mkv three.mkv
@@ -60,6 +221,10 @@ EXAMPLE:
         > "TV Series S1E02 - Episode Title.mkv"
         > "TV Series S1E03 - Episode Title.mkv"
 
+    Any `/` in the template will create directories, relative to the output directory:
+        > tv-renamer -o ~/Videos -t "${Series}/Season ${Season}/${Series} - ${Season}x${Episode}"
+        > "~/Videos/TV Series/Season 1/TV Series - 1x01.mkv"
+
     The season name can also be automatically inferred:
         > "TV Series/Season1"
         > "TV Series/Season2"
@@ -1,33 +1,121 @@
 mod man;
 use backend::{self, Arguments, Season, ScanDir, TargetErr};
+use backend::plan::{ConflictPolicy, Duplicate, DuplicateResolution, PlanError, Rename, RenamePlan, Resolution};
+use backend::preflight;
 use backend::tokenizer;
+use backend::config::{self, ConfigError};
+use backend::metadata::{self, Series};
+use backend::rewrite::{self, RewriteError};
+use backend::rules::{self, RuleError};
+use backend::sonarr::{self, SonarrError};
+use backend::sanitize::{self, Policy};
 use self::man::MAN_PAGE;
 use std::env;
-use std::io::{self, Write, Read};
+use std::io::{self, Write};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
 use tvdb;
-use backend::{DRY_RUN, VERBOSE};
+use backend::{DRY_RUN, VERBOSE, REMOVE_EMPTY, VERIFY, KEEP_GOING, AIR_DATE_MTIME, EPISODE_START};
+use backend::checksum::{self, Verification};
+use backend::journal;
+use backend::transfer::TransferMode;
+#[cfg(unix)]
+use libc;
 
 const EP_NO_VAL: &'static str = "no value was set for the episode count.\n";
 const SR_NO_VAL: &'static str = "no value was set for the series name.\n";
 const SN_NO_VAL: &'static str = "no value was set for the season number.\n";
 const PD_NO_VAL: &'static str = "no value was set for the pad length.\n";
 const TMP_NO_VAL:&'static str = "no value was set for the template.\n";
+const OD_NO_VAL: &'static str = "no value was set for the output directory.\n";
+const PR_NO_VAL: &'static str = "no value was set for the preset.\n";
+const SZ_NO_VAL: &'static str = "no value was set for the sanitize policy.\n";
+const NL_NO_VAL: &'static str = "no value was set for the name limit.\n";
+const ST_NO_VAL: &'static str = "no value was set for the season template.\n";
+const RT_NO_VAL: &'static str = "no value was set for the series template.\n";
+const RL_NO_VAL: &'static str = "no value was set for the rule.\n";
+const RW_NO_VAL: &'static str = "no value was set for the rewrite.\n";
+const OC_NO_VAL: &'static str = "no value was set for the conflict policy.\n";
+const TR_NO_VAL: &'static str = "no value was set for the transfer mode.\n";
+
+/// Copies of files which are at least this many bytes report their progress.
+const LARGE_COPY: u64 = 64 * 1024 * 1024;
+
+/// The exit status when some episodes were renamed, but others failed.
+const EXIT_PARTIAL: i32 = 2;
+
+/// The outcome of every episode within a run, which is reported as a table when `--keep-going` is enabled.
+#[derive(Default)]
+struct Summary {
+    renamed:   usize,
+    unchanged: usize,
+    skipped:   usize,
+    failures:  Vec<(PathBuf, String)>
+}
+
+impl Summary {
+    /// Records a path which could not be renamed, along with the reason why.
+    fn fail(&mut self, path: &Path, reason: String) {
+        self.failures.push((path.to_path_buf(), reason));
+    }
+
+    /// Prints the number of episodes with each outcome, followed by a row for each failure.
+    fn print(&self, stdout: &mut io::Stdout) {
+        let _ = writeln!(stdout, "\nSUMMARY");
+        let counts = [("renamed", self.renamed), ("unchanged", self.unchanged), ("skipped", self.skipped),
+            ("failed", self.failures.len())];
+        for &(outcome, count) in &counts {
+            let _ = writeln!(stdout, "    {:<10}{:>6}", outcome, count);
+        }
+        if self.failures.is_empty() { return }
+
+        let paths = self.failures.iter()
+            .map(|(path, _)| format!("{:?}", backend::shorten_path(path)))
+            .collect::<Vec<String>>();
+        let width = paths.iter().map(|path| path.chars().count()).max().unwrap_or(0);
+        let _ = writeln!(stdout, "\n    {:<width$}  REASON", "FILE", width = width);
+        for (path, (_, reason)) in paths.iter().zip(&self.failures) {
+            let _ = writeln!(stdout, "    {:<width$}  {}", path, reason, width = width);
+        }
+    }
+
+    /// Distinguishes a run where everything succeeded, from one where only some episodes were renamed, and from
+    /// one where nothing was renamed.
+    fn exit_status(&self) -> i32 {
+        if self.failures.is_empty() { 0 } else if self.renamed > 0 { EXIT_PARTIAL } else { 1 }
+    }
+}
 
 pub fn interface<A: Iterator<Item = String>>(args: A) {
     let stderr = &mut io::stderr();
 
+    // Reverting a previous run is handled by the `undo` subcommand.
+    let mut args = args.peekable();
+    if args.peek().is_some_and(|argument| argument == "undo") {
+        args.next();
+        undo(stderr, args);
+        return
+    }
+
     // Default CLI arguments
     let mut arguments = Arguments {
-        flags:          0,
-        season_index:   1,
-        episode_index:  1,
-        pad_length:     2,
-        base_directory: String::with_capacity(256),
-        series_name:    String::with_capacity(64),
-        template:       tokenizer::default_template(),
+        flags:            0,
+        season_index:     1,
+        episode_index:    1,
+        pad_length:       2,
+        base_directory:   String::with_capacity(256),
+        output_directory: None,
+        series_name:      String::with_capacity(64),
+        template:         tokenizer::default_template(),
+        sanitize:         Policy::Posix,
+        name_limit:       sanitize::NAME_MAX,
+        season_template:  None,
+        series_template:  None,
+        rules:            Vec::new(),
+        rewrites:         Vec::new(),
+        on_conflict:      ConflictPolicy::Ask,
+        transfer:         TransferMode::Rename,
     };
 
     // Attempt to parse the input arguments and act upon any errors that are returned
@@ -39,6 +127,30 @@ pub fn interface<A: Iterator<Item = String>>(args: A) {
             ParseError::NoSeriesName             => { let _ = stderr.write(SN_NO_VAL.as_bytes()); },
             ParseError::NoTemplate               => { let _ = stderr.write(TMP_NO_VAL.as_bytes()); },
             ParseError::NoPadLength              => { let _ = stderr.write(PD_NO_VAL.as_bytes()); },
+            ParseError::NoOutputDirectory        => { let _ = stderr.write(OD_NO_VAL.as_bytes()); },
+            ParseError::NoPreset                 => { let _ = stderr.write(PR_NO_VAL.as_bytes()); },
+            ParseError::UnknownPreset(value)     => { let _ = write!(stderr, "preset, `{}`, does not exist\n", value); },
+            ParseError::Config(why)              => { let _ = write!(stderr, "{}\n", why); },
+            ParseError::Sonarr(why)              => { let _ = write!(stderr, "invalid template: {}\n", why); },
+            ParseError::NoSanitizePolicy         => { let _ = stderr.write(SZ_NO_VAL.as_bytes()); },
+            ParseError::InvalidSanitizePolicy(value) => { let _ = write!(stderr, "sanitize policy, `{}`, is not valid\n", value); },
+            ParseError::NoNameLimit              => { let _ = stderr.write(NL_NO_VAL.as_bytes()); },
+            ParseError::NameLimitIsNaN(value)    => { let _ = write!(stderr, "name limit, `{}`, is not a number\n", value); },
+            ParseError::NameLimitOutOfRange(value) => {
+                let _ = write!(stderr, "name limit, `{}`, must be between {} and {} bytes\n", value,
+                    sanitize::MIN_NAME_LIMIT, sanitize::NAME_MAX);
+            },
+            ParseError::NoSeasonTemplate         => { let _ = stderr.write(ST_NO_VAL.as_bytes()); },
+            ParseError::NoSeriesTemplate         => { let _ = stderr.write(RT_NO_VAL.as_bytes()); },
+            ParseError::NoRule                   => { let _ = stderr.write(RL_NO_VAL.as_bytes()); },
+            ParseError::Rule(why)                => { let _ = write!(stderr, "invalid rule: {}\n", why); },
+            ParseError::NoRewrite                => { let _ = stderr.write(RW_NO_VAL.as_bytes()); },
+            ParseError::Rewrite(why)             => { let _ = write!(stderr, "invalid rewrite: {}\n", why); },
+            ParseError::NoConflictPolicy         => { let _ = stderr.write(OC_NO_VAL.as_bytes()); },
+            ParseError::InvalidConflictPolicy(value) => { let _ = write!(stderr, "conflict policy, `{}`, is not valid\n", value); },
+            ParseError::NoTransferMode           => { let _ = stderr.write(TR_NO_VAL.as_bytes()); },
+            ParseError::InvalidTransferMode(value) => { let _ = write!(stderr, "transfer mode, `{}`, is not valid\n", value); },
+            ParseError::UnsupportedTransferMode(value) => { let _ = write!(stderr, "transfer mode, `{}`, is not supported on this platform\n", value); },
             ParseError::EpisodeIndexIsNaN(value) => { let _ = write!(stderr, "episode index, `{}`, is not a number\n", value); },
             ParseError::SeriesIndexIsNaN(value)  => { let _ = write!(stderr, "series index, `{}`, is not a number\n", value); },
             ParseError::PadLengthIsNaN(value)    => { let _ = write!(stderr, "pad length, `{}`, is not a number\n", value); },
@@ -50,102 +162,539 @@ pub fn interface<A: Iterator<Item = String>>(args: A) {
         process::exit(1);
     }
 
-    // Collect a list of episodes within a directory and rename them.
-    match backend::scan_directory(&arguments.base_directory, arguments.season_index) {
-        // If the directory contains episodes, rename the episodes.
-        Ok(ScanDir::Episodes(season)) => rename_season(stderr, &season, &arguments, arguments.episode_index),
-        // If the directory contains seasons, rename the episodes in each season.
-        Ok(ScanDir::Seasons(seasons)) => for season in seasons { rename_season(stderr, &season, &arguments, 1); },
+    // Collect a list of episodes within a directory.
+    let episodes = match backend::scan_directory(&arguments.base_directory, arguments.season_index) {
+        Ok(episodes) => episodes,
         // If an error occurred, print an error and exit.
         Err(why) => {
             let _ = writeln!(stderr, "tv-renamer: {}", why);
             process::exit(1);
         }
-    }
-}
+    };
 
-/// Renames all of the episodes in given season
-fn rename_season(stderr: &mut io::Stderr, season: &Season, arguments: &Arguments, episode_no: u16) {
-    let stdout = &mut io::stdout();
-    let mut episode_no = episode_no;
+    // Verify the checksums embedded within the names of the episodes before any episode is renamed.
+    if arguments.flags & VERIFY != 0 && !verify_episodes(stderr, &episodes, &arguments) {
+        let _ = stderr.write_all(b"tv-renamer: stopping the renaming process due to checksum mismatches.\n");
+        process::exit(1);
+    }
 
     // TVDB
     let api = tvdb::Tvdb::new("0629B785CE550C8D");
-    let series_id = match api.search(&arguments.series_name, "en") {
-        Ok(result) => result[0].seriesid,
-        Err(_)     => {
+    let series = match metadata::search_series(&api, &arguments.series_name) {
+        Some(series) => series,
+        None         => {
             let _ = write!(stderr, "tv-renamer: invalid TV series: {}\n", &arguments.series_name);
             process::exit(1);
         }
     };
 
-    for source in &season.episodes {
-        match backend::collect_target(source, season.season_no, episode_no, arguments, &api, series_id) {
-            Ok(target) => {
-                // If the target exists, do not overwrite the target without first asking if it is OK.
-                if target.exists() {
-                    let stderr = io::stderr();
-                    let mut stderr = stderr.lock();
-                    let _ = writeln!(stderr, "tv-renamer: episode to be renamed already exists:\n{:?}\nIs it okay to overwrite? (y/n)", &target);
-                    let mut input = [b'n'; 1];
-
-                    if let Err(why) = io::stdin().read_exact(&mut input) {
-                        let _ = writeln!(stderr, "tv-renamer: error reading from standard input: {:?}", why);
-                        process::exit(1);
-                    }
+    // Every season is planned and checked before any episode is renamed.
+    let (seasons, episode_no, has_season_directories) = match episodes {
+        ScanDir::Episodes(season) => (vec![season], arguments.episode_index, false),
+        ScanDir::Seasons(seasons) => (seasons, 1, true)
+    };
+    // When keeping going, a season or episode which fails is recorded in the summary, and the rest continue.
+    let keep_going = arguments.flags & KEEP_GOING != 0;
+    let mut summary = Summary::default();
+    let mut plans = Vec::with_capacity(seasons.len());
+    for season in &seasons {
+        match plan_season(stderr, season, &arguments, episode_no, &api, &series, &mut summary) {
+            Some(plan) => plans.push(plan),
+            None => if !keep_going { process::exit(1) }
+        }
+    }
+    for plan in &plans { print_plan(plan, &arguments); }
+
+    // The season directories and the series directory are renamed once all of the episodes have been renamed. The
+    // directories are left in place when the episodes within them are kept, such as when they are copied.
+    let mut directories = if has_season_directories && arguments.transfer.removes_source() {
+        match plan_directories(stderr, &seasons, &arguments, &series, &mut summary) {
+            Some(directories) => directories,
+            None => if !keep_going { process::exit(1) } else { Vec::new() }
+        }
+    } else {
+        Vec::new()
+    };
+
+    // Every problem that would cause a rename to fail is reported at once, and nothing is renamed if any are found,
+    // unless the affected episodes and directories are to be left out instead.
+    if keep_going {
+        for (rename, problem) in preflight::exclude(&mut plans) {
+            let _ = writeln!(stderr, "tv-renamer: {}", problem);
+            summary.fail(&rename.source, problem.to_string());
+        }
+        for problem in preflight::check_directories(&directories) {
+            let _ = writeln!(stderr, "tv-renamer: {}", problem);
+            for (source, _) in directories.iter().filter(|(_, target)| problem.affects_directory(target)) {
+                summary.fail(source, problem.to_string());
+            }
+            directories.retain(|(_, target)| !problem.affects_directory(target));
+        }
+    } else {
+        let mut problems = preflight::check(&plans);
+        problems.extend(preflight::check_directories(&directories));
+        if !problems.is_empty() {
+            for problem in &problems { let _ = writeln!(stderr, "tv-renamer: {}", problem); }
+            let _ = writeln!(stderr, "tv-renamer: found {} problems, so nothing was renamed.", problems.len());
+            process::exit(1);
+        }
+    }
+
+    for plan in &plans {
+        summary.unchanged += plan.renames.iter().filter(|rename| rename.is_unchanged()).count();
+        summary.skipped += plan.skipped.len();
+        if arguments.flags & DRY_RUN != 0 {
+            summary.renamed += plan.renames.iter().filter(|rename| !rename.is_unchanged()).count();
+        }
+    }
+
+    // Every rename which is applied is collected, so that the run may be recorded in the undo journal. A season
+    // which fails is reverted, and the remaining seasons are only applied when keeping going.
+    let mut applied = Vec::new();
+    let mut previous = Vec::new();
+    let mut succeeded = true;
+    if arguments.flags & DRY_RUN == 0 {
+        for plan in plans {
+            if !apply_plan(stderr, plan, &mut applied, &mut previous, &mut summary) {
+                succeeded = false;
+                if !keep_going { break }
+            }
+        }
+    }
+
+    // Remove any source directories that were left empty by the renaming process.
+    if succeeded && arguments.flags & REMOVE_EMPTY != 0 && arguments.flags & DRY_RUN == 0 {
+        let sources = applied.iter().map(|(source, _)| source);
+        if let Err(why) = backend::remove_empty_directories(Path::new(&arguments.base_directory), sources) {
+            let _ = writeln!(stderr, "tv-renamer: unable to remove empty directories: {}", why);
+        }
+    }
+
+    // Once all of the episodes have been renamed, rename the season directories and the series directory.
+    if succeeded && !directories.is_empty() {
+        rename_directories(stderr, directories, &arguments, &mut applied, &mut summary);
+    }
+
+    if !applied.is_empty() {
+        match journal::record(&arguments.series_name, arguments.transfer, &applied, &previous) {
+            Ok(id) => if arguments.flags & VERBOSE != 0 {
+                let _ = writeln!(io::stdout(), "recorded as journal entry {}, which may be reverted with `tv-renamer undo`",
+                    id);
+            },
+            Err(why) => {
+                let _ = writeln!(stderr, "tv-renamer: unable to record the renames in the journal: {}", why);
+            }
+        }
+    }
+
+    if keep_going { summary.print(&mut io::stdout()); }
+    let status = summary.exit_status();
+    if status != 0 { process::exit(status); }
+}
 
-                    if input[0] != b'y' {
-                        let _ = stderr.write_all(b"tv-renamer: stopping the renaming process.\n");
+/// Reverts the renames of a previous run, as recorded by the undo journal.
+fn undo<A: Iterator<Item = String>>(stderr: &mut io::Stderr, mut args: A) {
+    let stdout = &mut io::stdout();
+    let mut id = None;
+    while let Some(argument) = args.next() {
+        match argument.as_str() {
+            "--last" => id = None,
+            "--id" => match args.next().map(|value| value.parse::<u32>().map_err(|_| value)) {
+                Some(Ok(value)) => id = Some(value),
+                Some(Err(value)) => {
+                    let _ = writeln!(stderr, "tv-renamer: journal ID, `{}`, is not a number", value);
+                    process::exit(1);
+                },
+                None => {
+                    let _ = writeln!(stderr, "tv-renamer: no value was set for the journal ID.");
+                    process::exit(1);
+                }
+            },
+            "--list" => {
+                match journal::entries() {
+                    Ok(entries) => for entry in entries {
+                        let _ = writeln!(stdout, "{}: {} ({} renames), {}", entry.id, entry.series,
+                            entry.renames.len(), journal::format_timestamp(entry.timestamp));
+                    },
+                    Err(why) => {
+                        let _ = writeln!(stderr, "tv-renamer: {}", why);
                         process::exit(1);
                     }
                 }
+                return
+            },
+            _ => {
+                let _ = writeln!(stderr, "tv-renamer: invalid argument: `{}`", argument);
+                process::exit(1);
+            }
+        }
+    }
 
-                // If dry run or verbose is enabled, print the action being taken
-                if arguments.flags & (DRY_RUN + VERBOSE) != 0 {
-                    let _ = stdout.write(b"\x1b[1m\x1b[32m");
-                    let _ = write!(stdout, "{:?}", backend::shorten_path(&source));
-                    let _ = stdout.write(b"\x1b[0m -> ");
-                    let _ = stdout.write(b"\x1b[1m\x1b[32m");
-                    let _ = write!(stdout, "{:?}", backend::shorten_path(&target));
-                    let _ = stdout.write(b"\x1b[0m\n");
+    match journal::undo(id) {
+        Ok(entry) => {
+            for rename in entry.renames.iter().rev() {
+                if entry.transfer.removes_source() {
+                    print_rename(stdout, &rename.target, &rename.source);
+                } else {
+                    let _ = writeln!(stdout, "removed {:?}", backend::shorten_path(&rename.target));
                 }
+            }
+            let _ = writeln!(stdout, "reverted journal entry {} for {}, from {}", entry.id, entry.series,
+                journal::format_timestamp(entry.timestamp));
+        },
+        Err(why) => {
+            let _ = writeln!(stderr, "tv-renamer: {}", why);
+            process::exit(1);
+        }
+    }
+}
 
-                // If dry run is not enabled, rename the file
-                if arguments.flags & DRY_RUN == 0 {
-                    if let Err(cause) = fs::rename(&source, &target) {
-                        let _ = writeln!(stderr, "tv-renamer: rename failed: {:?}", cause.to_string());
-                        process::exit(1);
-                    }
+/// Prints the action being taken when renaming a source to a target.
+fn print_rename(stdout: &mut io::Stdout, source: &Path, target: &Path) {
+    let _ = stdout.write(b"\x1b[1m\x1b[32m");
+    let _ = write!(stdout, "{:?}", backend::shorten_path(source));
+    let _ = stdout.write(b"\x1b[0m -> ");
+    let _ = stdout.write(b"\x1b[1m\x1b[32m");
+    let _ = write!(stdout, "{:?}", backend::shorten_path(target));
+    let _ = stdout.write(b"\x1b[0m\n");
+}
+
+/// Computes the target of each season directory according to the season template, and then of the series directory
+/// according to the series template, so that they may be checked along with the episodes. Directories which are
+/// already named correctly are left out. Returns `None` if a directory could not be named, which is recorded in the
+/// summary.
+fn plan_directories(stderr: &mut io::Stderr, seasons: &[Season], arguments: &Arguments, series: &Series,
+    summary: &mut Summary) -> Option<Vec<(PathBuf, PathBuf)>>
+{
+    let mut renames = Vec::new();
+
+    if let Some(ref template) = arguments.season_template {
+        for season in seasons {
+            match backend::season_directory_target(season, template, arguments, series) {
+                Ok(target) => renames.push((season.directory.clone(), target)),
+                Err(_) => {
+                    let reason = format!("unable to name the directory of season {}", season.season_no);
+                    let _ = writeln!(stderr, "tv-renamer: {}", reason);
+                    summary.fail(&season.directory, reason);
+                    return None;
+                }
+            }
+        }
+    }
+
+    if let Some(ref template) = arguments.series_template {
+        let directory = PathBuf::from(&arguments.base_directory);
+        match backend::series_directory_target(&directory, template, arguments, series) {
+            Ok(target) => renames.push((directory, target)),
+            Err(_) => {
+                let reason = String::from("unable to name the directory of the series");
+                let _ = writeln!(stderr, "tv-renamer: {}", reason);
+                summary.fail(&directory, reason);
+                return None;
+            }
+        }
+    }
+
+    renames.retain(|(source, target)| source != target);
+    Some(renames)
+}
+
+/// Renames the season directories and the series directory, which have already passed the preflight checks.
+/// Directories which no longer exist are skipped. If a directory cannot be renamed, the directories which were
+/// already renamed are reverted, so that the episodes remain where the journal records them, and the failure is
+/// recorded in the summary. Otherwise, each directory which is renamed is appended to `applied`.
+fn rename_directories(stderr: &mut io::Stderr, renames: Vec<(PathBuf, PathBuf)>, arguments: &Arguments,
+    applied: &mut Vec<(PathBuf, PathBuf)>, summary: &mut Summary)
+{
+    let stdout = &mut io::stdout();
+    let mut renamed: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for (source, target) in renames {
+        if !source.is_dir() { continue }
+
+        if arguments.flags & (DRY_RUN + VERBOSE) != 0 { print_rename(stdout, &source, &target); }
+        if arguments.flags & DRY_RUN != 0 { continue }
+
+        let result = if target.exists() {
+            Err(format!("{:?} already exists", backend::shorten_path(&target)))
+        } else {
+            fs::rename(&source, &target).map_err(|cause| cause.to_string())
+        };
+
+        if let Err(reason) = result {
+            let _ = writeln!(stderr, "tv-renamer: unable to rename the directory {:?}: {}", source, reason);
+            summary.fail(&source, reason);
+            for (source, target) in renamed.iter().rev() {
+                if let Err(cause) = fs::rename(target, source) {
+                    let _ = writeln!(stderr, "tv-renamer: unable to revert {:?} to {:?}: {}", target, source, cause);
+                    summary.fail(target, format!("unable to revert to {:?}", backend::shorten_path(source)));
+                    applied.push((source.clone(), target.clone()));
                 }
+            }
+            let _ = writeln!(stderr, "tv-renamer: the episodes were renamed, but the directories were left in place.");
+            return;
+        }
+        renamed.push((source, target));
+    }
+    applied.extend(renamed);
+}
 
+/// Compares the checksum embedded within each episode's name against its contents, and reports any mismatches.
+/// Returns `false` if any of the checksums did not match.
+fn verify_episodes(stderr: &mut io::Stderr, episodes: &ScanDir, arguments: &Arguments) -> bool {
+    let stdout = &mut io::stdout();
+    let mut verified = true;
+    for episode in episodes.episodes() {
+        match checksum::verify_checksum(episode) {
+            Ok(Verification::Match(value)) => if arguments.flags & VERBOSE != 0 {
+                let _ = writeln!(stdout, "{:?}: checksum {:08X} matches", backend::shorten_path(episode), value);
+            },
+            Ok(Verification::Mismatch { expected, actual }) => {
+                let _ = writeln!(stderr, "tv-renamer: {:?}: checksum mismatch: expected {:08X}, computed {:08X}",
+                    backend::shorten_path(episode), expected, actual);
+                verified = false;
             },
+            Ok(Verification::NoChecksum) => (),
             Err(why) => {
-                let _ = stderr.write(b"tv-renamer: ");
-                match why {
-                    // The episode number was unable to be found in the TV series.
-                    TargetErr::EpisodeDoesNotExist => {
-                        let _ = writeln!(stderr, "unable to find episode {}", episode_no);
-                    },
-                    TargetErr::Extension => {
-                        let _ = writeln!(stderr, "unable to get extension");
-                    },
-                    TargetErr::Parent => {
-                        let _ = writeln!(stderr, "unable to get parent filepath");
-                    }
-                }
-                process::exit(1);
+                let _ = writeln!(stderr, "tv-renamer: unable to read {:?}: {}", backend::shorten_path(episode), why);
+                verified = false;
+            }
+        }
+    }
+    verified
+}
+
+/// Asks on standard input what should be done with an episode whose target already exists.
+fn ask_conflict(rename: &Rename) -> Resolution {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let _ = writeln!(stderr, "tv-renamer: episode to be renamed already exists:\n{:?}\n\
+        Overwrite it, skip the episode, add a suffix, or abort? (o/s/u/a)", &rename.target);
+
+    let mut input = String::new();
+    if let Err(why) = io::stdin().read_line(&mut input) {
+        let _ = writeln!(stderr, "tv-renamer: error reading from standard input: {:?}", why);
+        return Resolution::Abort;
+    }
+
+    match input.trim() {
+        "o" | "y" => Resolution::Overwrite,
+        "s"       => Resolution::Skip,
+        "u"       => Resolution::Suffix,
+        _         => Resolution::Abort
+    }
+}
+
+/// Asks on standard input what should be done with several episodes which would be renamed to the same target.
+fn ask_duplicate(duplicate: &Duplicate) -> DuplicateResolution {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let _ = writeln!(stderr, "tv-renamer: {} episodes would be renamed to {:?}:", duplicate.candidates.len(),
+        &duplicate.target);
+    for (number, candidate) in duplicate.candidates.iter().enumerate() {
+        let _ = writeln!(stderr, "    {}) {}", number + 1, candidate.describe());
+    }
+    let _ = writeln!(stderr, "Keep all of them with a suffix, keep only one by its number, or abort? (u/1-{}/a)",
+        duplicate.candidates.len());
+
+    let mut input = String::new();
+    if let Err(why) = io::stdin().read_line(&mut input) {
+        let _ = writeln!(stderr, "tv-renamer: error reading from standard input: {:?}", why);
+        return DuplicateResolution::Abort;
+    }
+
+    match input.trim() {
+        "u" => DuplicateResolution::Suffix,
+        number => match number.parse::<usize>() {
+            Ok(number) if number >= 1 && number <= duplicate.candidates.len() => DuplicateResolution::Keep(number - 1),
+            _ => DuplicateResolution::Abort
+        }
+    }
+}
+
+/// Reports an episode whose target could not be computed, and records it in the summary.
+fn report_plan_error(stderr: &mut io::Stderr, error: PlanError, summary: &mut Summary) {
+    let PlanError { source, episode_no, why } = error;
+    let reason = match why {
+        // The episode number was unable to be found in the TV series.
+        TargetErr::EpisodeDoesNotExist => format!("unable to find episode {}", episode_no),
+        TargetErr::Extension => String::from("unable to get extension"),
+        TargetErr::Parent => String::from("unable to get parent filepath"),
+        TargetErr::NameTooLong => format!("the name of episode {} does not fit within the name limit", episode_no),
+        TargetErr::Unsafe(why) => format!("refusing to rename episode {}: {}", episode_no, why),
+        TargetErr::Unreadable(why) => format!("unable to read {:?}: {}", source, why)
+    };
+    let _ = writeln!(stderr, "tv-renamer: {}", reason);
+    summary.fail(&source, reason);
+}
+
+/// Computes the target of every episode in the given season, and resolves the episodes which would be renamed to
+/// the same target, or whose targets already exist, according to the conflict policy. When keeping going, episodes
+/// whose targets could not be computed are left out of the plan. Returns `None` if the season cannot be renamed,
+/// and every error is recorded in the summary.
+fn plan_season(stderr: &mut io::Stderr, season: &Season, arguments: &Arguments, episode_no: u16,
+    api: &tvdb::Tvdb, series: &Series, summary: &mut Summary) -> Option<RenamePlan>
+{
+    let mut plan = if arguments.flags & KEEP_GOING != 0 {
+        let (plan, errors) = RenamePlan::for_season_partial(season, episode_no, arguments, api, series);
+        for error in errors { report_plan_error(stderr, error, summary); }
+        plan
+    } else {
+        match RenamePlan::for_season(season, episode_no, arguments, api, series) {
+            Ok(plan) => plan,
+            Err(error) => {
+                report_plan_error(stderr, error, summary);
+                return None;
             }
         }
-        episode_no += 1;
+    };
+
+    if let Err(target) = plan.resolve_duplicates(arguments.on_conflict, ask_duplicate) {
+        let _ = writeln!(stderr, "tv-renamer: more than one episode would be renamed to {:?}, stopping the renaming \
+            process for season {}.", target, season.season_no);
+        summary.fail(&target, format!("more than one episode would be renamed to this, so season {} was not renamed",
+            season.season_no));
+        return None;
+    }
+
+    if let Err(target) = plan.resolve_conflicts(arguments.on_conflict, ask_conflict) {
+        let _ = writeln!(stderr, "tv-renamer: {:?} already exists, stopping the renaming process for season {}.",
+            target, season.season_no);
+        summary.fail(&target, format!("already exists, so season {} was not renamed", season.season_no));
+        return None;
+    }
+    Some(plan)
+}
+
+/// Prints the renames within the plan if dry run or verbose is enabled, and always reports the episodes which were
+/// skipped.
+fn print_plan(plan: &RenamePlan, arguments: &Arguments) {
+    let stdout = &mut io::stdout();
+    if arguments.flags & (DRY_RUN + VERBOSE) != 0 {
+        for rename in &plan.renames {
+            // Episodes which were previously renamed and are still named correctly are skipped.
+            if rename.is_unchanged() {
+                let _ = writeln!(stdout, "{:?} is already named correctly", backend::shorten_path(&rename.source));
+                continue
+            }
+
+            print_rename(stdout, &rename.source, &rename.target);
+            if let Some((ref original, ref rewritten)) = rename.title_rewrite {
+                let _ = writeln!(stdout, "    title rewritten: {:?} -> {:?}", original, rewritten);
+            }
+        }
+    }
+
+    // Episodes which were skipped are always reported, so that they are not mistaken for renamed episodes.
+    for rename in &plan.skipped {
+        let _ = writeln!(stdout, "skipped {:?}, rather than renaming it to {:?}", backend::shorten_path(&rename.source),
+            backend::shorten_path(&rename.target));
     }
 }
 
+/// Whether standard error is a terminal, on which the progress of copies can be redrawn in place.
+#[cfg(unix)]
+fn stderr_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDERR_FILENO) == 1 }
+}
+
+/// Progress is not shown on other platforms, where whether standard error is a terminal is not determined.
+#[cfg(not(unix))]
+fn stderr_is_terminal() -> bool { false }
+
+/// Applies the renames within the plan of a season, which has already passed the preflight checks. If a rename
+/// fails, the renames within the season are reverted. Each episode which is renamed is appended to `applied`, and
+/// every episode is counted in the summary as either renamed or failed. The previous modification time of each
+/// original file whose time was changed is appended to `previous`. Returns `false` if an error was reported.
+fn apply_plan(stderr: &mut io::Stderr, plan: RenamePlan, applied: &mut Vec<(PathBuf, PathBuf)>,
+    previous: &mut Vec<(PathBuf, i64)>, summary: &mut Summary) -> bool
+{
+    // The progress of large copies is only shown on a terminal, where each report replaces the last.
+    let terminal = stderr_is_terminal();
+    let mut shown = None;
+    let progress = |source: &Path, copied: u64, total: u64| {
+        if !terminal || total < LARGE_COPY { return }
+        let percent = copied * 100 / total;
+        if shown == Some(percent) { return }
+        shown = Some(percent);
+        let stderr = &mut io::stderr();
+        let _ = write!(stderr, "\rcopying {:?}: {}%", backend::shorten_path(source), percent);
+        if copied == total { let _ = writeln!(stderr); }
+    };
+
+    // The previous modification times are only returned once the whole season has been renamed.
+    let failure = match plan.apply(progress) {
+        Ok(times) => { previous.extend(times); None },
+        Err(why) => Some(why)
+    };
+
+    if let Some(why) = failure {
+        let _ = writeln!(stderr, "tv-renamer: rename of {:?} to {:?} failed: {:?}", why.source, why.target,
+            why.why.to_string());
+        summary.fail(&why.source, format!("renaming it to {:?} failed: {}", backend::shorten_path(&why.target),
+            why.why));
+        if why.rolled_back() {
+            let _ = writeln!(stderr, "tv-renamer: reverted the {} episodes that were renamed", why.reverted);
+        } else {
+            let _ = writeln!(stderr, "tv-renamer: reverted {} episodes, but {} could not be reverted:",
+                why.reverted, why.rollback_failures.len());
+            for (source, target, cause) in &why.rollback_failures {
+                let _ = writeln!(stderr, "    {:?} -> {:?}: {}", target, source, cause);
+            }
+            // Renames which could not be reverted are journaled in the order they were applied, so that they
+            // may be undone later.
+            applied.extend(why.rollback_failures.iter().rev()
+                .map(|(source, target, _)| (source.clone(), target.clone())));
+        }
+
+        // Every other episode in the season is either still renamed, because it could not be reverted, or has been
+        // rolled back, so that each episode is counted exactly once.
+        for rename in plan.renames.iter().filter(|rename| !rename.is_unchanged() && rename.source != why.source) {
+            let failure = why.rollback_failures.iter()
+                .find(|(from, to, _)| *to == rename.target || *from == rename.source);
+            match failure {
+                Some((_, to, _)) if *to == rename.target => summary.renamed += 1,
+                Some((_, to, _)) => summary.fail(&rename.source,
+                    format!("rolled back, but left at {:?}", backend::shorten_path(to))),
+                None => summary.fail(&rename.source, String::from("rolled back"))
+            }
+        }
+        return false;
+    }
+
+    summary.renamed += plan.renames.iter().filter(|rename| !rename.is_unchanged()).count();
+    applied.extend(plan.renames.into_iter()
+        .filter(|rename| !rename.is_unchanged())
+        .map(|rename| (rename.source, rename.target)));
+    true
+}
+
 enum ParseError {
     NoEpisodeIndex,
     NoSeriesIndex,
     NoSeriesName,
     NoTemplate,
     NoPadLength,
+    NoOutputDirectory,
+    NoPreset,
+    UnknownPreset(String),
+    Config(ConfigError),
+    Sonarr(SonarrError),
+    NoSanitizePolicy,
+    InvalidSanitizePolicy(String),
+    NoNameLimit,
+    NameLimitIsNaN(String),
+    NameLimitOutOfRange(usize),
+    NoSeasonTemplate,
+    NoSeriesTemplate,
+    NoRule,
+    Rule(RuleError),
+    NoRewrite,
+    Rewrite(RewriteError),
+    NoConflictPolicy,
+    InvalidConflictPolicy(String),
+    NoTransferMode,
+    InvalidTransferMode(String),
+    UnsupportedTransferMode(String),
     EpisodeIndexIsNaN(String),
     SeriesIndexIsNaN(String),
     PadLengthIsNaN(String),
@@ -172,6 +721,7 @@ fn parse_arguments <A: Iterator<Item = String>> (
                     let value = iterator.next().ok_or(ParseError::NoEpisodeIndex)?;
                     arguments.episode_index = value.parse::<u16>()
                         .map_err(|_| ParseError::EpisodeIndexIsNaN(value))?;
+                    arguments.flags |= EPISODE_START;
                 },
                 "-n" | "--series-name" => {
                     arguments.series_name.push_str(&iterator.next().ok_or(ParseError::NoSeriesName)?);
@@ -183,13 +733,69 @@ fn parse_arguments <A: Iterator<Item = String>> (
                 },
                 "-t" | "--template" => {
                     let value = iterator.next().ok_or(ParseError::NoTemplate)?;
-                    arguments.template = tokenizer::tokenize_template(&value);
+                    arguments.template = sonarr::tokenize(&value).map_err(ParseError::Sonarr)?;
+                },
+                "--season-template" => {
+                    let value = iterator.next().ok_or(ParseError::NoSeasonTemplate)?;
+                    arguments.season_template = Some(sonarr::tokenize(&value).map_err(ParseError::Sonarr)?);
+                },
+                "--series-template" => {
+                    let value = iterator.next().ok_or(ParseError::NoSeriesTemplate)?;
+                    arguments.series_template = Some(sonarr::tokenize(&value).map_err(ParseError::Sonarr)?);
+                },
+                "--rule" => {
+                    let value = iterator.next().ok_or(ParseError::NoRule)?;
+                    arguments.rules.push(rules::parse_rule(&value).map_err(ParseError::Rule)?);
+                },
+                "--rewrite" => {
+                    let value = iterator.next().ok_or(ParseError::NoRewrite)?;
+                    arguments.rewrites.push(rewrite::parse_rewrite(&value, None).map_err(ParseError::Rewrite)?);
+                },
+                "-P" | "--preset" => {
+                    let value = iterator.next().ok_or(ParseError::NoPreset)?;
+                    let template = config::preset(&value).map_err(ParseError::Config)?
+                        .ok_or(ParseError::UnknownPreset(value))?;
+                    arguments.template = sonarr::tokenize(&template).map_err(ParseError::Sonarr)?;
                 },
                 "-p" | "--pad-length" => {
                     let value = iterator.next().ok_or(ParseError::NoPadLength)?;
                     arguments.pad_length = value.parse::<u8>()
                         .map_err(|_| ParseError::PadLengthIsNaN(value))?;
                 },
+                "-S" | "--sanitize" => {
+                    let value = iterator.next().ok_or(ParseError::NoSanitizePolicy)?;
+                    arguments.sanitize = Policy::from_name(&value)
+                        .ok_or(ParseError::InvalidSanitizePolicy(value))?;
+                },
+                "--on-conflict" => {
+                    let value = iterator.next().ok_or(ParseError::NoConflictPolicy)?;
+                    arguments.on_conflict = ConflictPolicy::from_name(&value)
+                        .ok_or(ParseError::InvalidConflictPolicy(value))?;
+                },
+                "--transfer" => {
+                    let value = iterator.next().ok_or(ParseError::NoTransferMode)?;
+                    arguments.transfer = match TransferMode::from_name(&value) {
+                        Some(mode) if mode.is_supported() => mode,
+                        Some(_) => return Err(ParseError::UnsupportedTransferMode(value)),
+                        None => return Err(ParseError::InvalidTransferMode(value))
+                    };
+                },
+                "-l" | "--name-limit" => {
+                    let value = iterator.next().ok_or(ParseError::NoNameLimit)?;
+                    arguments.name_limit = value.parse::<usize>()
+                        .map_err(|_| ParseError::NameLimitIsNaN(value))?;
+                    if arguments.name_limit < sanitize::MIN_NAME_LIMIT || arguments.name_limit > sanitize::NAME_MAX {
+                        return Err(ParseError::NameLimitOutOfRange(arguments.name_limit));
+                    }
+                },
+                "-o" | "--output-directory" => {
+                    let value = iterator.next().ok_or(ParseError::NoOutputDirectory)?;
+                    arguments.output_directory = Some(PathBuf::from(value));
+                },
+                "-r" | "--remove-empty" => arguments.flags |= REMOVE_EMPTY,
+                "-c" | "--verify" => arguments.flags |= VERIFY,
+                "-k" | "--keep-going" => arguments.flags |= KEEP_GOING,
+                "--mtime-from-air-date" => arguments.flags |= AIR_DATE_MTIME,
                 "-v" | "--verbose" => arguments.flags |= VERBOSE,
                 _ => return Err(ParseError::InvalidArgument(argument))
             }
@@ -200,6 +806,10 @@ fn parse_arguments <A: Iterator<Item = String>> (
         }
     }
 
+    // Rules given on the command line are evaluated before the rules from the configuration file.
+    arguments.rules.extend(config::rules().map_err(ParseError::Config)?);
+    arguments.rewrites.extend(config::rewrites().map_err(ParseError::Config)?);
+
     // Set to current working directory if no directory argument is given.
     if arguments.base_directory.is_empty() {
         let directory = env::current_dir().map_err(|_| ParseError::NoCWD)?;
@@ -1,14 +1,26 @@
-use backend::{self, Arguments, ScanDir, Season, tokenizer, DRY_RUN};
+use backend::{self, Arguments, ReadDirError, ScanDir, Season, TargetErr, DRY_RUN};
+use backend::plan::{ConflictPolicy, Duplicate, DuplicateResolution, PlanError, Rename, RenamePlan, Resolution};
+use backend::preflight::{self, Problem};
+use backend::{config, metadata};
+use backend::metadata::Series;
+use backend::checksum::{self, Verification};
+use backend::journal;
+use backend::sonarr;
+use backend::sanitize::{self, Policy};
+use backend::transfer::{TransferMode, TRANSFER_MODES};
+use backend::validate::UnsafeTarget;
 
 use gdk::enums::key;
 use gtk::prelude::*;
 use gtk::{
-    self, Builder, Button, Entry, FileChooserDialog, ListStore,
+    self, Builder, Button, ComboBoxText, Entry, FileChooserDialog, ListStore, MessageDialog,
     SpinButton, TreeView, TreeViewColumn, Type, Window, WindowType
 };
 use std::error::Error;
-use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
 use tvdb;
 
 /// Allow drag-and-drop support in the directory entry text field by fixing the URI generated by dropped files.
@@ -32,9 +44,14 @@ pub fn interface() {
     let window: Window                  = builder.get_object("main_window").unwrap();
     let preview_button: Button          = builder.get_object("preview_button").unwrap();
     let rename_button: Button           = builder.get_object("rename_button").unwrap();
+    let verify_button: Button           = builder.get_object("verify_button").unwrap();
+    let undo_button: Button             = builder.get_object("undo_button").unwrap();
     let series_name_entry: Entry        = builder.get_object("series_name_entry").unwrap();
     let series_directory_entry: Entry   = builder.get_object("series_directory_entry").unwrap();
     let template_entry: Entry           = builder.get_object("template_entry").unwrap();
+    let preset_combo: ComboBoxText      = builder.get_object("preset_combo").unwrap();
+    let sanitize_combo: ComboBoxText    = builder.get_object("sanitize_combo").unwrap();
+    let transfer_combo: ComboBoxText    = builder.get_object("transfer_combo").unwrap();
     let series_directory_button: Button = builder.get_object("series_directory_button").unwrap();
     let episode_spin_button: SpinButton = builder.get_object("episode_spin_button").unwrap();
     let season_spin_button: SpinButton  = builder.get_object("season_spin_button").unwrap();
@@ -45,7 +62,7 @@ pub fn interface() {
 
     // TreeView's List Store
     // Link these up to the preview_tree and then start renaming
-    let preview_list = ListStore::new(&[Type::String, Type::String]);
+    let preview_list = ListStore::new(&[Type::String, Type::String, Type::String]);
 
     // A simple macro for adding a column to the preview tree.
     macro_rules! add_column {
@@ -63,11 +80,35 @@ pub fn interface() {
     // Create and append the Before column to the preview tree
     add_column!(preview_tree, "Before", 0);
     add_column!(preview_tree, "After", 1);
+    add_column!(preview_tree, "Title Rewrite", 2);
 
     // Connect the preview_list to the preview tree
     preview_tree.set_model(Some(&preview_list));
     preview_tree.set_headers_visible(true);
 
+    // Transfer modes which are not supported on this platform are removed from the transfer combo box, whose items
+    // are listed in the same order.
+    for (position, mode) in TRANSFER_MODES.iter().enumerate().rev() {
+        if !mode.is_supported() { transfer_combo.remove(position as i32); }
+    }
+
+    // Fill the preset combo box with the built-in and user-defined presets, and replace the template when one is chosen.
+    match config::presets() {
+        Ok(presets) => {
+            for &(ref name, _) in &presets { preset_combo.append_text(name); }
+            let template_entry = template_entry.clone();
+            preset_combo.connect_changed(move |combo| {
+                let template = combo.get_active_text()
+                    .and_then(|name| presets.iter().find(|&&(ref key, _)| key == &name));
+                if let Some(&(_, ref template)) = template { template_entry.set_text(template); }
+            });
+        },
+        Err(why) => {
+            info_bar.set_message_type(gtk::MessageType::Error);
+            notification_label.set_text(&why.to_string());
+        }
+    }
+
     // A simple macro that is shared among all widgets that trigger the action to either
     // update the preview or rename the TV series.
     macro_rules! rename_action {
@@ -81,6 +122,8 @@ pub fn interface() {
             let info_bar            = info_bar.clone();
             let notification_label  = notification_label.clone();
             let template_entry      = template_entry.clone();
+            let sanitize_combo      = sanitize_combo.clone();
+            let transfer_combo      = transfer_combo.clone();
             $widget.connect_clicked(move |_| {
                 if $dialog {
                     // Open file chooser dialog to modify series_directory_entry.
@@ -97,15 +140,46 @@ pub fn interface() {
                     }
                     dialog.destroy();
                 }
+                let template = match sonarr::tokenize(&template_entry.get_text().unwrap_or_default()) {
+                    Ok(template) => template,
+                    Err(why) => {
+                        info_bar.set_message_type(gtk::MessageType::Error);
+                        notification_label.set_text(&format!("Invalid template: {}", why));
+                        info_bar.show();
+                        return
+                    }
+                };
+
+                let (rules, rewrites) = match config::rules().and_then(|rules| Ok((rules, config::rewrites()?))) {
+                    Ok(config) => config,
+                    Err(why) => {
+                        info_bar.set_message_type(gtk::MessageType::Error);
+                        notification_label.set_text(&why.to_string());
+                        info_bar.show();
+                        return
+                    }
+                };
+
                 if let Some(directory) = directory_entry.get_text() {
                     let mut program = &mut Arguments {
-                        flags:          if $dry_run { DRY_RUN } else { 0 },
-                        base_directory: parse_directory(&directory),
-                        series_name:    series_entry.get_text().unwrap_or_default(),
-                        season_index:   season_spin_button.get_value_as_int() as u8,
-                        episode_index:  episode_spin_button.get_value_as_int() as u16,
-                        pad_length:     2,
-                        template:       tokenizer::tokenize_template(template_entry.get_text().unwrap().as_str())
+                        flags:            if $dry_run { DRY_RUN } else { 0 },
+                        base_directory:   parse_directory(&directory),
+                        output_directory: None,
+                        series_name:      series_entry.get_text().unwrap_or_default(),
+                        season_index:     season_spin_button.get_value_as_int() as u8,
+                        episode_index:    episode_spin_button.get_value_as_int() as u16,
+                        pad_length:       2,
+                        template:         template,
+                        sanitize:         sanitize_combo.get_active_text()
+                            .and_then(|name| Policy::from_name(&name)).unwrap_or(Policy::Posix),
+                        name_limit:       sanitize::NAME_MAX,
+                        season_template:  None,
+                        series_template:  None,
+                        rules:            rules,
+                        rewrites:         rewrites,
+                        on_conflict:      ConflictPolicy::Ask,
+                        transfer:         transfer_combo.get_active_text()
+                            .and_then(|name| TransferMode::from_name(&name)).unwrap_or(TransferMode::Rename)
                     };
 
                     if program.series_name.is_empty() {
@@ -127,6 +201,28 @@ pub fn interface() {
     rename_action!(series_directory_button, true, true);
     rename_action!(rename_button, false, false);
 
+    { // Verify the checksums of the episodes in the chosen directory
+        let directory_entry    = series_directory_entry.clone();
+        let season_spin_button = season_spin_button.clone();
+        let preview_list       = preview_list.clone();
+        let info_bar           = info_bar.clone();
+        let notification_label = notification_label.clone();
+        verify_button.connect_clicked(move |button| {
+            let directory = parse_directory(&directory_entry.get_text().unwrap_or_default());
+            if !directory.is_empty() {
+                let season_index = season_spin_button.get_value_as_int() as u8;
+                verify_series(&directory, season_index, button, &preview_list, &info_bar, &notification_label);
+            }
+        });
+    }
+
+    { // Revert the most recent rename that was recorded in the undo journal
+        let preview_list       = preview_list.clone();
+        let info_bar           = info_bar.clone();
+        let notification_label = notification_label.clone();
+        undo_button.connect_clicked(move |_| undo_last(&preview_list, &info_bar, &notification_label));
+    }
+
     { // Hide the Info Bar when the Info Bar is closed
         let info_bar = info_bar.clone();
         info_button.connect_clicked(move |_| {
@@ -138,8 +234,8 @@ pub fn interface() {
     window.set_wmclass ("tv-renamer", "Tv-renamer");
 
     window.show_all();
-    info_bar.hide();
-
+    // Keep the Info Bar visible if an error occurred while the window was being constructed.
+    if notification_label.get_text().map_or(true, |text| text.is_empty()) { info_bar.hide(); }
 
     // Quit the program when the program has been exited
     window.connect_delete_event(|_, _| {
@@ -156,50 +252,185 @@ pub fn interface() {
     gtk::main();
 }
 
-/// Attempt to rename all of the seasons within a given series
+/// Attempt to rename all of the seasons within a given series. The renames which were applied are recorded in the
+/// undo journal.
 fn rename_series(args: &Arguments, preview_list: &ListStore, info_bar: &gtk::InfoBar, notification_label: &gtk::Label) {
     preview_list.clear();
-    match backend::scan_directory(&args.base_directory, args.season_index) {
-        Ok(ScanDir::Episodes(season)) => {
-            match rename_season(&season, args.episode_index, args, preview_list) {
-                Ok(_) => {
-                    if args.flags & DRY_RUN != 0 { return }
-                    info_bar.set_message_type(gtk::MessageType::Info);
-                    notification_label.set_text("Rename Success");
-                },
-                Err(why) => match_rename_error(info_bar, notification_label, why, args)
-            }
-        },
-        Ok(ScanDir::Seasons(seasons))  => {
-            for season in seasons {
-                match rename_season(&season, 1, args, preview_list) {
-                    Ok(_) => {
-                        if args.flags & DRY_RUN != 0 { return }
-                        info_bar.set_message_type(gtk::MessageType::Info);
-                        notification_label.set_text("Rename Success");
-                    },
-                    Err(why) => {
-                        match_rename_error(info_bar, notification_label, why, args);
-                        break
-                    }
-                }
+    let mut applied = Vec::new();
+    match rename_seasons(args, preview_list, &mut applied, info_bar, notification_label) {
+        Ok(_) if args.flags & DRY_RUN != 0 => return,
+        Ok(skipped) => rename_success(info_bar, notification_label, skipped),
+        Err(why) => match_rename_error(info_bar, notification_label, why, args)
+    }
+
+    if !applied.is_empty() {
+        // Modification times are only changed from the command line, so there are none to restore.
+        if let Err(why) = journal::record(&args.series_name, args.transfer, &applied, &[]) {
+            info_bar.set_message_type(gtk::MessageType::Warning);
+            notification_label.set_text(&format!("Renamed, but unable to record the renames for undo: {}", why));
+        }
+    }
+    info_bar.show();
+}
+
+/// Plans every season within the series and updates the preview, and then checks every plan before any episode is
+/// renamed. If no problems were found and dry run is not enabled, each season is renamed. Returns the number of
+/// episodes which were skipped because their targets were taken.
+fn rename_seasons(args: &Arguments, preview_list: &ListStore, applied: &mut Vec<(PathBuf, PathBuf)>,
+    info_bar: &gtk::InfoBar, notification_label: &gtk::Label) -> Result<usize, RenameErr>
+{
+    let seasons = match backend::scan_directory(&args.base_directory, args.season_index) {
+        Ok(ScanDir::Episodes(season)) => vec![(season, args.episode_index)],
+        Ok(ScanDir::Seasons(seasons)) => seasons.into_iter().map(|season| (season, 1)).collect(),
+        Err(why) => return Err(RenameErr::ScanFailed(why))
+    };
+
+    // TVDB
+    let api = tvdb::Tvdb::new("0629B785CE550C8D");
+    let series = metadata::search_series(&api, &args.series_name).ok_or(RenameErr::SeriesLookupFailed)?;
+
+    let mut plans = Vec::with_capacity(seasons.len());
+    for &(ref season, episode_no) in &seasons {
+        let plan = plan_season(season, episode_no, args, &api, &series)?;
+        preview_plan(preview_list, &plan);
+        plans.push(plan);
+    }
+
+    let problems = preflight::check(&plans);
+    if !problems.is_empty() { return Err(RenameErr::Preflight(problems)) }
+
+    let skipped: usize = plans.iter().map(|plan| plan.skipped.len()).sum();
+    if args.flags & DRY_RUN == 0 {
+        for plan in plans { apply_plan(plan, applied, info_bar, notification_label)?; }
+    }
+    Ok(skipped)
+}
+
+/// Reports a successful rename in the `InfoBar`, along with the number of episodes that were skipped because their
+/// targets were taken.
+fn rename_success(info_bar: &gtk::InfoBar, notification_label: &gtk::Label, skipped: usize) {
+    if skipped == 0 {
+        info_bar.set_message_type(gtk::MessageType::Info);
+        notification_label.set_text("Rename Success");
+    } else {
+        info_bar.set_message_type(gtk::MessageType::Warning);
+        notification_label.set_text(&format!("Renamed, but skipped {} episodes", skipped));
+    }
+}
+
+/// Reverts the most recent run that was recorded in the undo journal, and lists the reverted episodes in the
+/// preview.
+fn undo_last(preview_list: &ListStore, info_bar: &gtk::InfoBar, notification_label: &gtk::Label) {
+    match journal::undo(None) {
+        Ok(entry) => {
+            preview_list.clear();
+            for rename in entry.renames.iter().rev() {
+                update_preview(preview_list, &rename.target, &rename.source, None);
             }
+            info_bar.set_message_type(gtk::MessageType::Info);
+            notification_label.set_text(&format!("Reverted the renames of {} from {}", entry.series,
+                journal::format_timestamp(entry.timestamp)));
         },
         Err(why) => {
             info_bar.set_message_type(gtk::MessageType::Error);
-            notification_label.set_text(why.description());
+            notification_label.set_text(&why.to_string());
         }
     }
     info_bar.show();
 }
 
+/// Verifies the checksums embedded within the names of every episode in the directory. Checksums are computed on a
+/// worker thread, so that large files do not stall the interface, and each result is appended to the preview as it
+/// arrives.
+fn verify_series(directory: &str, season_index: u8, button: &Button, preview_list: &ListStore,
+    info_bar: &gtk::InfoBar, notification_label: &gtk::Label)
+{
+    preview_list.clear();
+    let episodes: Vec<PathBuf> = match backend::scan_directory(directory, season_index) {
+        Ok(episodes) => episodes.episodes().into_iter().cloned().collect(),
+        Err(why) => {
+            info_bar.set_message_type(gtk::MessageType::Error);
+            notification_label.set_text(why.description());
+            info_bar.show();
+            return
+        }
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for episode in episodes {
+            let result = checksum::verify_checksum(&episode);
+            if sender.send((episode, result)).is_err() { break }
+        }
+    });
+
+    // Only one verification may run at a time.
+    button.set_sensitive(false);
+
+    let button             = button.clone();
+    let preview_list       = preview_list.clone();
+    let info_bar           = info_bar.clone();
+    let notification_label = notification_label.clone();
+    let mut mismatches     = 0;
+    gtk::timeout_add(100, move || {
+        loop {
+            match receiver.try_recv() {
+                Ok((episode, result)) => {
+                    let status = match result {
+                        Ok(Verification::Match(value)) => format!("{:08X} OK", value),
+                        Ok(Verification::Mismatch { expected, actual }) => {
+                            mismatches += 1;
+                            format!("Mismatch: expected {:08X}, computed {:08X}", expected, actual)
+                        },
+                        Ok(Verification::NoChecksum) => String::from("No checksum"),
+                        Err(why) => {
+                            mismatches += 1;
+                            format!("Unreadable: {}", why)
+                        }
+                    };
+                    let name = episode.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                    preview_list.insert_with_values(None, &[0, 1], &[&name, &status]);
+                },
+                Err(TryRecvError::Empty) => return gtk::Continue(true),
+                Err(TryRecvError::Disconnected) => {
+                    button.set_sensitive(true);
+                    if mismatches == 0 {
+                        info_bar.set_message_type(gtk::MessageType::Info);
+                        notification_label.set_text("Verification Success");
+                    } else {
+                        info_bar.set_message_type(gtk::MessageType::Error);
+                        notification_label.set_text(&format!("{} episodes failed verification", mismatches));
+                    }
+                    info_bar.show();
+                    return gtk::Continue(false)
+                }
+            }
+        }
+    });
+}
+
 /// If a rename error occurs, write the message to the `InfoBar`.
 fn match_rename_error(info_bar: &gtk::InfoBar, notification_label: &gtk::Label, why: RenameErr, args: &Arguments) {
     info_bar.set_message_type(gtk::MessageType::Error);
     let message = match why {
-        RenameErr::RenameFailed(source, target) => format!("Could not rename {:?} to {:?}", source, target),
+        RenameErr::RenameFailed(source, target, true) => {
+            format!("Could not rename {:?} to {:?}. The episodes that were renamed have been reverted.", source, target)
+        },
+        RenameErr::RenameFailed(source, target, false) => {
+            format!("Could not rename {:?} to {:?}, and some of the episodes that were renamed could not be reverted.",
+                source, target)
+        },
         RenameErr::TargetExists(path)           => format!("{:?} already exists", path),
+        RenameErr::DuplicateTarget(path)        => format!("More than one episode would be renamed to {:?}", path),
+        RenameErr::Preflight(problems)          => {
+            let problems: Vec<String> = problems.iter().map(|problem| problem.to_string()).collect();
+            format!("Nothing was renamed, as {} problems were found:\n{}", problems.len(), problems.join("\n"))
+        },
+        RenameErr::ScanFailed(why)              => why.to_string(),
         RenameErr::EpisodeDoesNotExist(episode) => format!("Episode {} could not be found on TheTVDB", episode),
+        RenameErr::UnsafeTarget(episode, why)   => format!("Refusing to rename episode {}: {}", episode, why),
+        RenameErr::NameTooLong(episode)         => format!("The name of episode {} is too long", episode),
+        RenameErr::Unreadable(path, why)        => format!("Could not read {:?}: {}", path, why),
         RenameErr::SeriesLookupFailed           => format!("{} could not be found on TheTVDB", &args.series_name)
     };
     notification_label.set_text(message.as_str());
@@ -207,39 +438,144 @@ fn match_rename_error(info_bar: &gtk::InfoBar, notification_label: &gtk::Label,
 
 enum RenameErr {
     TargetExists(PathBuf),
-    RenameFailed(PathBuf, PathBuf),
+    DuplicateTarget(PathBuf),
+    /// Every problem that was found by checking the plans before any episode was renamed.
+    Preflight(Vec<Problem>),
+    ScanFailed(ReadDirError),
+    /// The source and target which could not be renamed, and whether the renames before it were reverted.
+    RenameFailed(PathBuf, PathBuf, bool),
     EpisodeDoesNotExist(u16),
+    UnsafeTarget(u16, UnsafeTarget),
+    NameTooLong(u16),
+    Unreadable(PathBuf, io::Error),
     SeriesLookupFailed
 }
 
-/// Renames a given season and updates the preview for each episode renamed.
-/// If executed with `arguments.dry_run` set to true, the preview will be updated but the files will not be renamed.
-fn rename_season(season: &Season, episode_no: u16, arguments: &Arguments, preview_list: &ListStore)
-    -> Result<(), RenameErr>
+/// Asks the user what should be done with an episode whose target already exists.
+fn ask_conflict(rename: &Rename) -> Resolution {
+    const SKIP: i32 = 1;
+    const OVERWRITE: i32 = 2;
+    const SUFFIX: i32 = 3;
+
+    let message = format!("{} already exists.\nWhat should be done with {}?",
+        rename.target.display(), rename.source.display());
+    let dialog = MessageDialog::new(Some(&Window::new(WindowType::Popup)), gtk::DIALOG_MODAL,
+        gtk::MessageType::Question, gtk::ButtonsType::None, &message);
+    dialog.add_button("Abort", gtk::ResponseType::Cancel.into());
+    dialog.add_button("Skip", SKIP);
+    dialog.add_button("Add Suffix", SUFFIX);
+    dialog.add_button("Overwrite", OVERWRITE);
+
+    let resolution = match dialog.run() {
+        SKIP      => Resolution::Skip,
+        OVERWRITE => Resolution::Overwrite,
+        SUFFIX    => Resolution::Suffix,
+        _         => Resolution::Abort
+    };
+    dialog.destroy();
+    resolution
+}
+
+/// Asks the user what should be done with several episodes which would be renamed to the same target, listing the
+/// size and quality of each episode.
+fn ask_duplicate(duplicate: &Duplicate) -> DuplicateResolution {
+    const SUFFIX: i32 = 1;
+    // Each candidate is kept by a response which is offset by its index.
+    const KEEP: i32 = 100;
+
+    let candidates: Vec<String> = duplicate.candidates.iter().enumerate()
+        .map(|(index, candidate)| format!("{}. {}", index + 1, candidate.describe()))
+        .collect();
+    let message = format!("{} episodes would be renamed to {}:\n{}", duplicate.candidates.len(),
+        duplicate.target.display(), candidates.join("\n"));
+    let dialog = MessageDialog::new(Some(&Window::new(WindowType::Popup)), gtk::DIALOG_MODAL,
+        gtk::MessageType::Question, gtk::ButtonsType::None, &message);
+    dialog.add_button("Abort", gtk::ResponseType::Cancel.into());
+    for index in 0..duplicate.candidates.len() {
+        dialog.add_button(&format!("Keep Only {}", index + 1), KEEP + index as i32);
+    }
+    dialog.add_button("Keep All with Suffix", SUFFIX);
+
+    let response = dialog.run();
+    dialog.destroy();
+    match response {
+        SUFFIX => DuplicateResolution::Suffix,
+        response if response >= KEEP => DuplicateResolution::Keep((response - KEEP) as usize),
+        _ => DuplicateResolution::Abort
+    }
+}
+
+/// Computes the target of every episode within a given season, and resolves the episodes which would be renamed to
+/// the same target, or whose targets already exist, according to the conflict policy.
+fn plan_season(season: &Season, episode_no: u16, arguments: &Arguments, api: &tvdb::Tvdb, series: &Series)
+    -> Result<RenamePlan, RenameErr>
 {
-    let mut episode_no = episode_no;
+    let mut plan = RenamePlan::for_season(season, episode_no, arguments, api, series)
+        .map_err(|PlanError { source, episode_no, why }| match why {
+            TargetErr::Unsafe(why) => RenameErr::UnsafeTarget(episode_no, why),
+            TargetErr::Unreadable(why) => RenameErr::Unreadable(source, why),
+            TargetErr::NameTooLong => RenameErr::NameTooLong(episode_no),
+            _ => RenameErr::EpisodeDoesNotExist(episode_no)
+        })?;
 
-    // TVDB
-    let api = tvdb::Tvdb::new("0629B785CE550C8D");
-    let series_id = api.search(&arguments.series_name, "en").map_err(|_| RenameErr::SeriesLookupFailed)?[0].seriesid;
-
-    for source in &season.episodes {
-        let target = backend::collect_target(source, season.season_no, episode_no, arguments, &api, series_id)
-            .map_err(|_| RenameErr::EpisodeDoesNotExist(episode_no))?;
-        if target.exists() { return Err(RenameErr::TargetExists(source.clone())); }
-        update_preview(preview_list, source, &target);
-        if arguments.flags & DRY_RUN == 0 {
-            fs::rename(&source, &target).map_err(|_| RenameErr::RenameFailed(source.clone(), target))?;
-        }
-        episode_no += 1;
+    // The preview does not ask about conflicts, so that existing targets are reported rather than resolved.
+    if arguments.flags & DRY_RUN == 0 || arguments.on_conflict != ConflictPolicy::Ask {
+        plan.resolve_duplicates(arguments.on_conflict, ask_duplicate).map_err(RenameErr::DuplicateTarget)?;
+        plan.resolve_conflicts(arguments.on_conflict, ask_conflict).map_err(RenameErr::TargetExists)?;
     }
-    Ok(()) // Rename success
+    Ok(plan)
+}
+
+/// Appends every rename within the plan to the preview, along with the episodes which were skipped.
+fn preview_plan(preview_list: &ListStore, plan: &RenamePlan) {
+    for rename in &plan.renames {
+        update_preview(preview_list, &rename.source, &rename.target, rename.title_rewrite.clone());
+    }
+
+    for rename in &plan.skipped {
+        let src = rename.source.components().last().unwrap().as_os_str().to_string_lossy().into_owned();
+        let trg = rename.target.components().last().unwrap().as_os_str().to_string_lossy().into_owned();
+        preview_list.insert_with_values(None, &[0, 1, 2], &[&src, &format!("Skipped: {}", trg), &""]);
+    }
+}
+
+/// Applies the renames within the plan of a season, which has already passed the preflight checks. The progress of
+/// copies is shown in the `InfoBar`. Each episode which is renamed is appended to `applied`.
+fn apply_plan(plan: RenamePlan, applied: &mut Vec<(PathBuf, PathBuf)>, info_bar: &gtk::InfoBar,
+    notification_label: &gtk::Label) -> Result<(), RenameErr>
+{
+    let mut shown = None;
+    let progress = |source: &Path, copied: u64, total: u64| {
+        let percent = if total == 0 { 100 } else { copied * 100 / total };
+        if shown == Some(percent) { return }
+        shown = Some(percent);
+        let name = source.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        info_bar.set_message_type(gtk::MessageType::Info);
+        notification_label.set_text(&format!("Copying {}: {}%", name, percent));
+        info_bar.show();
+        // Copies run on the main thread, so pending events are handled to redraw the progress.
+        while gtk::events_pending() { gtk::main_iteration(); }
+    };
+
+    if let Err(why) = plan.apply(progress) {
+        // Renames which could not be reverted are journaled in the order they were applied, so that they may be
+        // undone later.
+        applied.extend(why.rollback_failures.iter().rev()
+            .map(|&(ref source, ref target, _)| (source.clone(), target.clone())));
+        return Err(RenameErr::RenameFailed(why.source, why.target, why.rollback_failures.is_empty()));
+    }
+
+    applied.extend(plan.renames.into_iter()
+        .filter(|rename| !rename.is_unchanged())
+        .map(|rename| (rename.source, rename.target)));
+    Ok(())
 }
 
 #[inline]
-/// Appends an episode to the preview list
-fn update_preview(preview_list: &ListStore, source: &Path, target: &Path) {
+/// Appends an episode to the preview list, along with the original and rewritten title if the title was rewritten.
+fn update_preview(preview_list: &ListStore, source: &Path, target: &Path, title_rewrite: Option<(String, String)>) {
     let src = source.components().last().unwrap().as_os_str().to_str().unwrap().to_string();
     let trg = target.components().last().unwrap().as_os_str().to_str().unwrap().to_string();
-    preview_list.insert_with_values(None, &[0, 1], &[&src, &trg]);
+    let title = title_rewrite.map(|(original, rewritten)| format!("{} → {}", original, rewritten)).unwrap_or_default();
+    preview_list.insert_with_values(None, &[0, 1, 2], &[&src, &trg, &title]);
 }
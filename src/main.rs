@@ -1,6 +1,9 @@
 extern crate tvdb;
 extern crate gtk;
 extern crate gdk;
+extern crate unicode_normalization;
+extern crate regex;
+extern crate libc;
 #[macro_use] extern crate quick_error;
 
 mod backend;